@@ -0,0 +1,71 @@
+//! End-to-end latency from a captured `SA_SayW` call to the frame arriving at
+//! a pipe client.
+//!
+//! Run with `cargo bench -p tts-air-capture --bench latency [-- ITERATIONS]`.
+//! Every iteration waits for its message so the coalescing window is included
+//! in full.
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use tts_air_capture::Backend;
+use tts_air_capture::Config;
+use tts_air_capture::Event;
+use tts_air_ipc::Transport;
+use tts_air_ipc::protocol;
+
+#[cfg(windows)]
+const PIPE: &str = "\\\\.\\pipe\\net.wartide.d4.tts-air-bench";
+#[cfg(unix)]
+const PIPE: &str = "/tmp/net.wartide.d4.tts-air-bench";
+
+fn main() {
+    let iterations = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(1000);
+    if iterations == 0 {
+        eprintln!("ITERATIONS needs to be at least 1");
+        std::process::exit(2);
+    }
+
+    let config = Config {
+        pipe: Some(PIPE.to_string()),
+        ..Config::default()
+    };
+    assert!(tts_air_capture::start(&config, Backend::Unknown));
+
+    let address = config.address(Backend::Unknown);
+    let mut pipe = loop {
+        match tts_air_ipc::Pipe::open(&address) {
+            Ok(pipe) => break pipe,
+            Err(_) => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    let mut decoder = protocol::Decoder::new();
+    protocol::handshake_client(&mut pipe, &mut decoder, "latency-bench", protocol::Replay::None)
+        .expect("handshake failed");
+
+    let text = "Legendary Two-Handed Sword".encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let mut buffer = [0; 0x1000];
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        unsafe {
+            tts_air_capture::send_wchar(text.as_ptr(), Event::Say);
+        }
+        let frame = protocol::recv_frame(&mut pipe, &mut decoder, &mut buffer).expect("recv failed");
+        samples.push(start.elapsed());
+        assert_eq!(frame.kind, protocol::Kind::Text);
+    }
+
+    samples.sort();
+    let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+    println!("coalescing window {:?}, max delay {:?}", config.window(), config.max_delay());
+    println!("{iterations} iterations from SA_SayW to pipe client:");
+    println!("  min {:?}", samples[0]);
+    println!("  p50 {:?}", percentile(50));
+    println!("  p90 {:?}", percentile(90));
+    println!("  p99 {:?}", percentile(99));
+    println!("  max {:?}", samples[samples.len() - 1]);
+}
@@ -0,0 +1,379 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::thread;
+
+use serde::Deserialize;
+use tts_air_ipc::Transport;
+use tts_air_ipc::control::ClientStatus;
+use tts_air_ipc::protocol;
+
+/// One message encoded as one or more frames.
+pub struct Encoded {
+    pub bytes: Vec<u8>,
+    pub frames: usize,
+}
+
+impl Encoded {
+    /// Copy with every frame flagged as replayed.
+    pub fn replayed(&self) -> Self {
+        let mut bytes = self.bytes.clone();
+        protocol::mark_replayed(&mut bytes);
+        Self {
+            bytes,
+            frames: self.frames,
+        }
+    }
+}
+
+/// What happens when a client queue is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Overflow {
+    /// Drop the oldest queued message to make room.
+    #[default]
+    DropOldest,
+    /// Disconnect the client.
+    Disconnect,
+}
+
+/// Connected pipe client with its own writer thread.
+///
+/// Messages are queued without blocking so a slow client never holds up
+/// capture or the other clients.
+pub struct Client {
+    id: u64,
+    queue: Arc<Queue>,
+    capacity: usize,
+    overflow: Overflow,
+    /// Shuts down the connection to wake up a writer stuck in a send and the
+    /// request reader.
+    shutdown: Option<Box<dyn Fn() + Send>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// Queued messages, flagged if the client asked for them.
+    messages: VecDeque<(Arc<Encoded>, bool)>,
+    /// Queued messages the client didn't ask for, which are limited by the
+    /// capacity.
+    live: usize,
+    closed: bool,
+    /// Frames dropped because the queue was full.
+    dropped: u64,
+}
+
+impl Client {
+    /// Start writing to `pipe` with `initial` queued before anything else.
+    ///
+    /// `initial` is not limited by `capacity`, like
+    /// [`Client::push_requested`].
+    pub fn spawn<T: Transport + 'static>(
+        id: u64,
+        pipe: T,
+        initial: Vec<Arc<Encoded>>,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Self {
+        let queue = Arc::new(Queue::default());
+        queue.state.lock().unwrap().messages.extend(initial.into_iter().map(|m| (m, true)));
+
+        let shutdown = match pipe.try_clone() {
+            Ok(handle) => Some(Box::new(move || {
+                if let Err(e) = handle.shutdown() {
+                    log::debug!("failed to shut down client #{id}: {e}");
+                }
+            }) as Box<dyn Fn() + Send>),
+            Err(e) => {
+                log::warn!("client #{id} can't be disconnected while stalled: {e}");
+                None
+            }
+        };
+
+        let writer = queue.clone();
+        thread::spawn(move || write(id, pipe, &writer));
+
+        Self {
+            id,
+            queue,
+            capacity: capacity.max(1),
+            overflow,
+            shutdown,
+        }
+    }
+
+    /// Queue `message` for sending. Returns false once the client is gone.
+    pub fn push(&self, message: &Arc<Encoded>) -> bool {
+        let Ok(mut state) = self.queue.state.lock() else {
+            return false;
+        };
+        if state.closed {
+            return false;
+        }
+
+        if state.live >= self.capacity {
+            match self.overflow {
+                Overflow::DropOldest => {
+                    if state.dropped == 0 {
+                        log::warn!("client #{} is falling behind, dropping oldest messages", self.id);
+                    }
+                    // requested messages are never dropped
+                    let oldest = state.messages.iter().position(|(_, requested)| !requested);
+                    let frames = oldest.and_then(|i| state.messages.remove(i)).map_or(0, |(m, _)| m.frames);
+                    state.live -= 1;
+                    state.dropped += frames as u64;
+                }
+                Overflow::Disconnect => {
+                    log::warn!("disconnecting client #{} for falling behind", self.id);
+                    drop(state);
+                    self.close();
+                    return false;
+                }
+            }
+        }
+
+        state.messages.push_back((message.clone(), false));
+        state.live += 1;
+        self.queue.ready.notify_one();
+        true
+    }
+
+    /// Queue `messages` requested by the client itself, which are not limited
+    /// by the capacity. Returns false once the client is gone.
+    pub fn push_requested(&self, messages: impl IntoIterator<Item = Arc<Encoded>>) -> bool {
+        let Ok(mut state) = self.queue.state.lock() else {
+            return false;
+        };
+        if state.closed {
+            return false;
+        }
+
+        state.messages.extend(messages.into_iter().map(|m| (m, true)));
+        self.queue.ready.notify_one();
+        true
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Frames dropped so far because the client fell behind.
+    pub fn dropped(&self) -> u64 {
+        self.queue.state.lock().map_or(0, |state| state.dropped)
+    }
+
+    pub fn status(&self) -> ClientStatus {
+        let (queued, dropped) = self.queue.state.lock()
+            .map_or((0, 0), |state| (state.messages.len() as u64, state.dropped));
+        ClientStatus {
+            id: self.id,
+            queued,
+            dropped,
+        }
+    }
+
+    /// Stop the writer and close the connection even if a send is stuck.
+    fn close(&self) {
+        if let Ok(mut state) = self.queue.state.lock() {
+            state.closed = true;
+            self.queue.ready.notify_one();
+        }
+        if let Some(shutdown) = &self.shutdown {
+            shutdown();
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn write<T: Transport>(id: u64, mut pipe: T, queue: &Queue) {
+    loop {
+        let message = {
+            let Ok(mut state) = queue.state.lock() else {
+                return;
+            };
+            loop {
+                if state.closed {
+                    return;
+                }
+                if let Some((message, requested)) = state.messages.pop_front() {
+                    if !requested {
+                        state.live -= 1;
+                    }
+                    break message;
+                }
+                state = match queue.ready.wait(state) {
+                    Ok(state) => state,
+                    Err(_) => return,
+                };
+            }
+        };
+
+        if let Err(e) = pipe.send(&message.bytes) {
+            log::error!("Transport::send error {e:?} for client #{id}");
+            if let Ok(mut state) = queue.state.lock() {
+                state.closed = true;
+            }
+            // stops the request reader too
+            let _ = pipe.shutdown();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use tts_air_ipc::IpcError;
+
+    /// Transport that blocks every send until released.
+    #[derive(Clone)]
+    struct Stalled {
+        entered: mpsc::Sender<()>,
+        release: Arc<Mutex<mpsc::Receiver<()>>>,
+        sent: mpsc::Sender<u8>,
+        shutdown: mpsc::Sender<()>,
+    }
+
+    impl Transport for Stalled {
+        fn open(_: &str) -> Result<Self, IpcError> {
+            Err(IpcError::InvalidPath)
+        }
+
+        fn recv(&mut self, _: &mut [u8]) -> Result<u64, IpcError> {
+            Err(IpcError::Disconnected)
+        }
+
+        fn send(&mut self, msg: &[u8]) -> Result<u64, IpcError> {
+            let _ = self.entered.send(());
+            self.release.lock().unwrap().recv().map_err(|_| IpcError::Disconnected)?;
+            self.sent.send(msg[0]).map_err(|_| IpcError::Disconnected)?;
+            Ok(msg.len() as u64)
+        }
+
+        fn try_clone(&self) -> Result<Self, IpcError> {
+            Ok(self.clone())
+        }
+
+        fn shutdown(&self) -> Result<(), IpcError> {
+            self.shutdown.send(()).map_err(|_| IpcError::Disconnected)
+        }
+
+        fn discover(_: &str) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    fn message(b: u8) -> Arc<Encoded> {
+        Arc::new(Encoded { bytes: vec![b], frames: 2 })
+    }
+
+    /// Client whose writer is stuck sending the initial message 0.
+    struct StalledClient {
+        client: Client,
+        release: mpsc::Sender<()>,
+        sent: mpsc::Receiver<u8>,
+        shutdown: mpsc::Receiver<()>,
+    }
+
+    fn stalled_client(capacity: usize, overflow: Overflow) -> StalledClient {
+        let (entered, entered_recv) = mpsc::channel();
+        let (release, release_recv) = mpsc::channel();
+        let (sent, sent_recv) = mpsc::channel();
+        let (shutdown, shutdown_recv) = mpsc::channel();
+        let pipe = Stalled { entered, release: Arc::new(Mutex::new(release_recv)), sent, shutdown };
+        let client = Client::spawn(0, pipe, vec![message(0)], capacity, overflow);
+        entered_recv.recv().unwrap();
+        StalledClient { client, release, sent: sent_recv, shutdown: shutdown_recv }
+    }
+
+    #[test]
+    fn drop_oldest_never_blocks() {
+        let stalled = stalled_client(2, Overflow::DropOldest);
+        for b in 1..=5 {
+            assert!(stalled.client.push(&message(b)));
+        }
+        assert_eq!(stalled.client.dropped(), 6);
+        assert_eq!(stalled.client.status(), ClientStatus { id: 0, queued: 2, dropped: 6 });
+
+        for _ in 0..3 {
+            stalled.release.send(()).unwrap();
+        }
+        let sent = (0..3).map(|_| stalled.sent.recv().unwrap()).collect::<Vec<_>>();
+        assert_eq!(sent, vec![0, 4, 5]);
+        assert!(stalled.shutdown.try_recv().is_err());
+    }
+
+    #[test]
+    fn requested_messages_ignore_capacity() {
+        let stalled = stalled_client(1, Overflow::Disconnect);
+        assert!(stalled.client.push_requested((1..=3).map(message)));
+        assert!(stalled.client.push(&message(4)));
+        assert_eq!(stalled.client.status().queued, 4);
+        assert!(!stalled.client.push(&message(5)));
+
+        let stalled = stalled_client(1, Overflow::DropOldest);
+        assert!(stalled.client.push(&message(1)));
+        assert!(stalled.client.push_requested([message(2)]));
+        assert!(stalled.client.push(&message(3)));
+        for _ in 0..3 {
+            stalled.release.send(()).unwrap();
+        }
+        let sent = (0..3).map(|_| stalled.sent.recv().unwrap()).collect::<Vec<_>>();
+        assert_eq!(sent, vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn disconnect_when_full() {
+        let stalled = stalled_client(1, Overflow::Disconnect);
+        assert!(stalled.client.push(&message(1)));
+        assert!(!stalled.client.push(&message(2)));
+        // the stuck send is woken up instead of waiting for the release
+        stalled.shutdown.recv().unwrap();
+        assert!(!stalled.client.push(&message(3)));
+        assert_eq!(stalled.client.dropped(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn peer_sees_disconnect() {
+        use tts_air_ipc::Listener;
+        use tts_air_ipc::UnixSocket;
+        use tts_air_ipc::UnixSocketListener;
+
+        let address = format!("/tmp/tts-air-client-test-{}\0", std::process::id());
+        let mut listener = UnixSocketListener::bind(&address).unwrap();
+        let mut peer = UnixSocket::open(&address).unwrap();
+        let pipe = listener.listen().unwrap();
+
+        // larger than the socket buffer so the writer is stuck while the peer
+        // isn't reading
+        let big = || Arc::new(Encoded { bytes: vec![0; 0x400_0000], frames: 1 });
+        let client = Client::spawn(0, pipe, vec![big()], 1, Overflow::Disconnect);
+        // the queue overflows whether or not the writer took the first one yet
+        assert!((0..3).any(|_| !client.push(&big())));
+
+        let mut buffer = vec![0; 0x10000];
+        let mut read = 0;
+        let end = loop {
+            match peer.recv(&mut buffer) {
+                Ok(len) => read += len,
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(end, IpcError::Disconnected);
+        assert!(read < 0x400_0000);
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+use std::time::Instant;
+
+/// Merges text events that arrive close together into one newline-joined
+/// message.
+///
+/// Time is only ever read from the arguments so the caller decides what clock
+/// is used.
+pub struct Coalescer {
+    /// Largest gap between two events of the same message.
+    window: Duration,
+    /// Most events merged into one message.
+    max_batch: usize,
+    /// Longest an event waits for more to merge with, measured from the first
+    /// event of a message.
+    max_delay: Duration,
+    pending: Option<Message>,
+    last: Option<Instant>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    pub text: String,
+    /// Capture time of the first merged event.
+    pub start: Instant,
+    /// Number of merged events.
+    pub count: usize,
+}
+
+impl Message {
+    pub fn new(text: String, start: Instant) -> Self {
+        Self {
+            text,
+            start,
+            count: 1,
+        }
+    }
+}
+
+impl Default for Coalescer {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(2), usize::MAX, Duration::from_millis(2))
+    }
+}
+
+impl Coalescer {
+    pub fn new(window: Duration, max_batch: usize, max_delay: Duration) -> Self {
+        Self {
+            window,
+            max_batch: max_batch.max(1),
+            max_delay,
+            pending: None,
+            last: None,
+        }
+    }
+
+    /// Change the durations used from now on, including for the pending
+    /// message.
+    pub fn set_window(&mut self, window: Duration, max_delay: Duration) {
+        self.window = window;
+        self.max_delay = max_delay;
+    }
+
+    /// Add an event captured at `at`.
+    ///
+    /// Returns the pending message if the event can't be merged into it.
+    pub fn push(&mut self, text: &str, at: Instant) -> Option<Message> {
+        let done = match (&mut self.pending, self.last) {
+            (Some(pending), Some(last)) if pending.count < self.max_batch
+                && at < last + self.window
+                && at < pending.start + self.max_delay =>
+            {
+                pending.text.push('\n');
+                pending.text.push_str(text);
+                pending.count += 1;
+                None
+            }
+            _ => self.pending.take(),
+        };
+
+        if self.pending.is_none() {
+            self.pending = Some(Message::new(text.to_string(), at));
+            self.last = Some(at);
+        } else {
+            self.last = self.last.max(Some(at));
+        }
+
+        done
+    }
+
+    /// Time when the pending message can no longer grow.
+    pub fn deadline(&self) -> Option<Instant> {
+        let pending = self.pending.as_ref()?;
+        if pending.count >= self.max_batch {
+            return Some(pending.start);
+        }
+
+        let last = self.last.unwrap_or(pending.start);
+        Some((last + self.window).min(pending.start + self.max_delay))
+    }
+
+    /// Take the pending message if its deadline passed at `now`.
+    pub fn poll(&mut self, now: Instant) -> Option<Message> {
+        if self.deadline()? <= now {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Take the pending message regardless of its deadline.
+    pub fn flush(&mut self) -> Option<Message> {
+        self.pending.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn merges_within_window() {
+        let t = Instant::now();
+        let mut c = Coalescer::new(ms(2), usize::MAX, ms(10));
+        assert_eq!(c.push("a", t), None);
+        assert_eq!(c.push("b", t + ms(1)), None);
+        assert_eq!(c.poll(t + ms(2)), None);
+        assert_eq!(c.poll(t + ms(3)), Some(Message {
+            text: "a\nb".into(),
+            start: t,
+            count: 2,
+        }));
+        assert_eq!(c.flush(), None);
+    }
+
+    #[test]
+    fn splits_outside_window() {
+        let t = Instant::now();
+        let mut c = Coalescer::new(ms(2), usize::MAX, ms(10));
+        assert_eq!(c.push("a", t), None);
+        let done = c.push("b", t + ms(2)).unwrap();
+        assert_eq!(done.text, "a");
+        assert_eq!(done.start, t);
+
+        let done = c.flush().unwrap();
+        assert_eq!(done.text, "b");
+        assert_eq!(done.start, t + ms(2));
+    }
+
+    #[test]
+    fn window_slides_until_max_delay() {
+        let t = Instant::now();
+        let mut c = Coalescer::new(ms(3), usize::MAX, ms(5));
+        assert_eq!(c.push("a", t), None);
+        assert_eq!(c.push("b", t + ms(2)), None);
+        assert_eq!(c.push("c", t + ms(4)), None);
+        assert_eq!(c.deadline(), Some(t + ms(5)));
+        let done = c.push("d", t + ms(5)).unwrap();
+        assert_eq!(done.text, "a\nb\nc");
+        assert_eq!(done.count, 3);
+        assert_eq!(c.deadline(), Some(t + ms(8)));
+    }
+
+    #[test]
+    fn max_batch() {
+        let t = Instant::now();
+        let mut c = Coalescer::new(ms(2), 2, ms(10));
+        assert_eq!(c.push("a", t), None);
+        assert_eq!(c.push("b", t), None);
+        assert_eq!(c.deadline(), Some(t));
+        assert_eq!(c.poll(t).unwrap().text, "a\nb");
+
+        assert_eq!(c.push("c", t), None);
+        assert_eq!(c.push("d", t), None);
+        assert_eq!(c.push("e", t).unwrap().text, "c\nd");
+        assert_eq!(c.flush().unwrap().text, "e");
+    }
+
+    #[test]
+    fn out_of_order_events() {
+        let t = Instant::now() + ms(10);
+        let mut c = Coalescer::new(ms(2), usize::MAX, ms(10));
+        assert_eq!(c.push("a", t), None);
+        assert_eq!(c.push("b", t - ms(1)), None);
+        assert_eq!(c.deadline(), Some(t + ms(2)));
+        assert_eq!(c.flush().unwrap().text, "a\nb");
+    }
+
+    #[test]
+    fn default_matches_fixed_two_ms() {
+        let t = Instant::now();
+        let mut c = Coalescer::default();
+        assert_eq!(c.push("a", t), None);
+        assert_eq!(c.push("b", t + Duration::from_micros(1999)), None);
+        assert_eq!(c.push("c", t + ms(2)).unwrap().text, "a\nb");
+    }
+
+    #[test]
+    fn empty() {
+        let mut c = Coalescer::default();
+        assert_eq!(c.deadline(), None);
+        assert_eq!(c.poll(Instant::now()), None);
+        assert_eq!(c.flush(), None);
+    }
+}
@@ -0,0 +1,326 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tts_air_ipc::protocol;
+use tts_air_ipc::protocol::Backend;
+
+use crate::client::Overflow;
+
+/// Name of the optional config file next to the capture library.
+pub const FILE_NAME: &str = "tts_air.toml";
+
+/// Settings of the capture library read from [`FILE_NAME`].
+///
+/// Every field is optional in the file:
+///
+/// ```toml
+/// pipe = '\\.\pipe\net.wartide.d4.tts-air-0'
+/// history = 32
+/// downstream = 'C:\Program Files\Screen Reader\saapi64.dll'
+///
+/// [coalesce]
+/// window_ms = 2
+/// max_delay_ms = 2
+/// max_batch = 16
+///
+/// [clients]
+/// queue = 64
+/// overflow = "drop-oldest"
+///
+/// [messages]
+/// chunk_size = 65535
+/// max_size = 16777216
+///
+/// [log]
+/// level = "info"
+/// path = 'C:\Users\me\tts_air.log'
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the capture library listens on, without NUL terminator.
+    ///
+    /// Defaults to the [`instance_address`](tts_air_ipc::instance_address)
+    /// of the process and backend, which is the only form clients discover.
+    pub pipe: Option<String>,
+    /// Number of recent messages kept for clients that connect late.
+    pub history: usize,
+    /// SAAPI library to forward calls to after capture.
+    pub downstream: Option<PathBuf>,
+    pub coalesce: Coalesce,
+    pub clients: Clients,
+    pub messages: Messages,
+    pub log: Log,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Coalesce {
+    /// Largest gap between two events of the same message.
+    pub window_ms: u64,
+    /// Longest the first event of a message waits for more.
+    pub max_delay_ms: u64,
+    /// Most events merged into one message. Unlimited if unset.
+    pub max_batch: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Clients {
+    /// Most messages queued for a client that isn't keeping up.
+    pub queue: usize,
+    /// `drop-oldest` or `disconnect` once the queue is full.
+    pub overflow: Overflow,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Messages {
+    /// Most payload bytes per frame, longer messages are split.
+    pub chunk_size: usize,
+    /// Longer messages are truncated.
+    pub max_size: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Log {
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+    /// Relative paths are relative to the working directory of the host process.
+    pub path: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            pipe: None,
+            history: 32,
+            downstream: None,
+            coalesce: Coalesce::default(),
+            clients: Clients::default(),
+            messages: Messages::default(),
+            log: Log::default(),
+        }
+    }
+}
+
+impl Default for Coalesce {
+    fn default() -> Self {
+        Self {
+            window_ms: 2,
+            max_delay_ms: 2,
+            max_batch: None,
+        }
+    }
+}
+
+impl Default for Clients {
+    fn default() -> Self {
+        Self {
+            queue: 64,
+            overflow: Overflow::DropOldest,
+        }
+    }
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self {
+            chunk_size: protocol::MAX_PAYLOAD,
+            max_size: protocol::MAX_MESSAGE,
+        }
+    }
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self {
+            level: if cfg!(debug_assertions) { "trace" } else { "off" }.to_string(),
+            path: PathBuf::from("tts_air.log"),
+        }
+    }
+}
+
+/// Upper bound of the coalescing durations since every message is delayed by
+/// them.
+pub(crate) const MAX_COALESCE_MS: u64 = 1000;
+/// Upper bound of [`Config::history`] and [`Clients::queue`] to keep memory
+/// use in the host bounded.
+const MAX_HISTORY: usize = 0x1000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    Parse(String),
+    Invalid(&'static str, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Parse(e) => write!(f, "{e}"),
+            ConfigError::Invalid(field, reason) => write!(f, "invalid `{field}`: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read the config file in `dir`. A missing file gives the defaults.
+    pub fn load(dir: &Path) -> Result<Self, ConfigError> {
+        let path = dir.join(FILE_NAME);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::Parse(format!("failed to read {path:?}: {e}"))),
+        }
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.pipe.as_ref().is_some_and(|pipe| pipe.is_empty() || pipe.contains('\0')) {
+            return Err(ConfigError::Invalid("pipe", "needs to be a non-empty address without NUL".to_string()));
+        }
+        if self.history > MAX_HISTORY {
+            return Err(ConfigError::Invalid("history", format!("{} exceeds {MAX_HISTORY}", self.history)));
+        }
+        if self.clients.queue == 0 || self.clients.queue > MAX_HISTORY {
+            return Err(ConfigError::Invalid("clients.queue", format!("needs to be between 1 and {MAX_HISTORY}")));
+        }
+        for (field, ms) in [
+            ("coalesce.window_ms", self.coalesce.window_ms),
+            ("coalesce.max_delay_ms", self.coalesce.max_delay_ms),
+        ] {
+            if ms > MAX_COALESCE_MS {
+                return Err(ConfigError::Invalid(field, format!("{ms} exceeds {MAX_COALESCE_MS}")));
+            }
+        }
+        if self.coalesce.max_batch == Some(0) {
+            return Err(ConfigError::Invalid("coalesce.max_batch", "needs to be at least 1".to_string()));
+        }
+        // clients reject anything larger
+        if self.messages.chunk_size == 0 || self.messages.chunk_size > protocol::MAX_PAYLOAD {
+            return Err(ConfigError::Invalid("messages.chunk_size", format!("needs to be between 1 and {}", protocol::MAX_PAYLOAD)));
+        }
+        if self.messages.max_size == 0 || self.messages.max_size > protocol::MAX_MESSAGE {
+            return Err(ConfigError::Invalid("messages.max_size", format!("needs to be between 1 and {}", protocol::MAX_MESSAGE)));
+        }
+        if self.log.level.parse::<log::LevelFilter>().is_err() {
+            return Err(ConfigError::Invalid("log.level", format!("unknown level {:?}", self.log.level)));
+        }
+        Ok(())
+    }
+
+    /// [`Config::pipe`] of the `backend` library in the NUL-terminated form
+    /// used by [`tts_air_ipc`].
+    pub fn address(&self, backend: Backend) -> String {
+        match &self.pipe {
+            Some(pipe) => format!("{pipe}\0"),
+            None => tts_air_ipc::instance_address(std::process::id(), backend),
+        }
+    }
+
+    pub fn log_level(&self) -> log::LevelFilter {
+        self.log.level.parse().unwrap_or(log::LevelFilter::Off)
+    }
+
+    pub fn window(&self) -> Duration {
+        Duration::from_millis(self.coalesce.window_ms)
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.coalesce.max_delay_ms)
+    }
+
+    pub fn max_batch(&self) -> usize {
+        self.coalesce.max_batch.unwrap_or(usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_is_default() {
+        assert_eq!(Config::parse(""), Ok(Config::default()));
+        let address = Config::default().address(Backend::Nvda);
+        assert_eq!(tts_air_ipc::instance_id(&address), Some((std::process::id(), Backend::Nvda)));
+    }
+
+    #[test]
+    fn parses_all_fields() {
+        let config = Config::parse(r#"
+            pipe = '\\.\pipe\other'
+            history = 4
+            downstream = 'C:\nvda\saapi64.dll'
+
+            [coalesce]
+            window_ms = 5
+            max_delay_ms = 20
+            max_batch = 3
+
+            [clients]
+            queue = 8
+            overflow = "disconnect"
+
+            [messages]
+            chunk_size = 512
+            max_size = 4096
+
+            [log]
+            level = "INFO"
+            path = "other.log"
+        "#).unwrap();
+
+        assert_eq!(config.address(Backend::Saapi), "\\\\.\\pipe\\other\0");
+        assert_eq!(config.history, 4);
+        assert_eq!(config.downstream, Some(PathBuf::from("C:\\nvda\\saapi64.dll")));
+        assert_eq!(config.window(), Duration::from_millis(5));
+        assert_eq!(config.max_delay(), Duration::from_millis(20));
+        assert_eq!(config.max_batch(), 3);
+        assert_eq!(config.clients, Clients { queue: 8, overflow: Overflow::Disconnect });
+        assert_eq!(config.messages, Messages { chunk_size: 512, max_size: 4096 });
+        assert_eq!(config.log_level(), log::LevelFilter::Info);
+        assert_eq!(config.log.path, PathBuf::from("other.log"));
+    }
+
+    #[test]
+    fn partial_tables_keep_defaults() {
+        let config = Config::parse("[coalesce]\nwindow_ms = 4").unwrap();
+        assert_eq!(config.window(), Duration::from_millis(4));
+        assert_eq!(config.max_delay(), Config::default().max_delay());
+        assert_eq!(config.max_batch(), usize::MAX);
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(matches!(Config::parse("pipe = 3"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("piep = 'typo'"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("history = -1"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("[clients]\noverflow = 'block'"), Err(ConfigError::Parse(_))));
+
+        let invalid = |text| match Config::parse(text) {
+            Err(ConfigError::Invalid(field, _)) => field,
+            res => panic!("expected invalid config for {text:?}, got {res:?}"),
+        };
+        assert_eq!(invalid("pipe = ''"), "pipe");
+        assert_eq!(invalid("history = 1000000"), "history");
+        assert_eq!(invalid("[coalesce]\nwindow_ms = 60000"), "coalesce.window_ms");
+        assert_eq!(invalid("[coalesce]\nmax_batch = 0"), "coalesce.max_batch");
+        assert_eq!(invalid("[clients]\nqueue = 0"), "clients.queue");
+        assert_eq!(invalid("[messages]\nchunk_size = 0"), "messages.chunk_size");
+        assert_eq!(invalid("[messages]\nchunk_size = 65536"), "messages.chunk_size");
+        assert_eq!(invalid("[messages]\nmax_size = 0"), "messages.max_size");
+        assert_eq!(invalid("[messages]\nmax_size = 16777217"), "messages.max_size");
+        assert_eq!(invalid("[log]\nlevel = 'loud'"), "log.level");
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+use tts_air_ipc::protocol::Replay;
+
+/// Ring buffer of the most recently broadcast messages.
+///
+/// Messages are kept as their encoded frames so a replay is sent exactly like
+/// the original broadcast.
+pub struct History<T> {
+    /// Most messages kept.
+    capacity: usize,
+    /// Sequence id and encoded frames in ascending sequence order.
+    entries: VecDeque<(u64, T)>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self::new(32)
+    }
+}
+
+impl<T> History<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Add a message, dropping the oldest one if full.
+    pub fn push(&mut self, seq: u64, frames: T) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((seq, frames));
+    }
+
+    /// Encoded frames of the messages requested by `replay`, oldest first.
+    pub fn replay(&self, replay: Replay) -> impl Iterator<Item = &T> {
+        let skip = match replay {
+            Replay::None => self.entries.len(),
+            Replay::Since(seq) => self.entries.partition_point(|(s, _)| *s <= seq),
+            Replay::Last(count) => self.entries.len().saturating_sub(count.try_into().unwrap_or(usize::MAX)),
+        };
+
+        self.entries.iter()
+            .skip(skip)
+            .map(|(_, frames)| frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(capacity: usize, seqs: std::ops::RangeInclusive<u64>) -> History<Vec<u8>> {
+        let mut h = History::new(capacity);
+        for seq in seqs {
+            h.push(seq, vec![seq as u8]);
+        }
+        h
+    }
+
+    fn replayed(h: &History<Vec<u8>>, replay: Replay) -> Vec<u8> {
+        h.replay(replay).flatten().copied().collect()
+    }
+
+    #[test]
+    fn keeps_most_recent() {
+        let h = history(3, 1..=5);
+        assert_eq!(replayed(&h, Replay::Last(u64::MAX)), vec![3, 4, 5]);
+        assert_eq!(replayed(&h, Replay::None), vec![]);
+        assert!(replayed(&history(0, 1..=5), Replay::Last(1)).is_empty());
+    }
+
+    #[test]
+    fn replay_since() {
+        let h = history(3, 1..=5);
+        assert_eq!(replayed(&h, Replay::Since(0)), vec![3, 4, 5]);
+        assert_eq!(replayed(&h, Replay::Since(3)), vec![4, 5]);
+        assert_eq!(replayed(&h, Replay::Since(5)), vec![]);
+        assert_eq!(replayed(&h, Replay::Since(9)), vec![]);
+    }
+
+    #[test]
+    fn replay_last() {
+        let h = history(3, 1..=5);
+        assert_eq!(replayed(&h, Replay::Last(0)), vec![]);
+        assert_eq!(replayed(&h, Replay::Last(2)), vec![4, 5]);
+    }
+}
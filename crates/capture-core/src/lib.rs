@@ -0,0 +1,684 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicIsize;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::Receiver;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use tts_air_ipc::Listener;
+use tts_air_ipc::Transport;
+use tts_air_ipc::control::Command;
+use tts_air_ipc::control::Response;
+use tts_air_ipc::protocol;
+use tts_air_ipc::protocol::Frame;
+use tts_air_ipc::protocol::Kind;
+use tts_air_ipc::protocol::ProtocolError;
+use tts_air_ipc::protocol::Replay;
+
+mod client;
+use client::Client;
+use client::Encoded;
+mod coalesce;
+use coalesce::Coalescer;
+use coalesce::Message;
+mod config;
+pub use config::Config;
+use config::ConfigError;
+mod history;
+use history::History;
+
+pub use tts_air_ipc::protocol::Backend;
+
+/// Screen reader call captured from the host process.
+pub enum Event {
+    Say(Box<[u16]>),
+    Braille(Box<[u16]>),
+    Stop,
+}
+
+/// Message to the broadcast thread.
+enum Input<T> {
+    /// Captured event with its capture time.
+    Event(Event, Instant),
+    /// Client that finished the handshake, with its id.
+    Connect(u64, T, Replay),
+    /// Request from a connected client with the client id and the request id.
+    Request(u64, u64, Result<Command, ProtocolError>),
+}
+
+/// Queues a captured event for the broadcast thread. Returns false once the
+/// thread is gone.
+type Pump = Box<dyn Fn(Event, Instant) -> bool + Send>;
+
+fn pump<T: Send + 'static>(send: Sender<Input<T>>) -> Pump {
+    Box::new(move |event, at| send.send(Input::Event(event, at)).is_ok())
+}
+
+static PUMP: Mutex<Option<Pump>> = Mutex::new(None);
+
+/// Number of messages sent as more than one frame.
+static SPLIT_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+const NAME: &str = concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION"));
+
+/// Module handle passed to [`init`].
+static MODULE: AtomicIsize = AtomicIsize::new(0);
+
+static CONFIG: OnceLock<(Config, Option<ConfigError>)> = OnceLock::new();
+
+/// Set by [`init`] when logging is turned off in the config file, so
+/// [`Command::SetLogLevel`] has no logger to change.
+static LOGGING_OFF: AtomicBool = AtomicBool::new(false);
+
+/// Settings from the config file next to the capture library.
+///
+/// The file is read on first use since that can't happen in DllMain. An
+/// invalid file is logged once logging is set up and the defaults are used.
+pub fn config() -> &'static Config {
+    &load_config().0
+}
+
+fn load_config() -> &'static (Config, Option<ConfigError>) {
+    CONFIG.get_or_init(|| {
+        let Some(dir) = sys::module_path(MODULE.load(Ordering::Relaxed))
+            .and_then(|path| Some(path.parent()?.to_path_buf()))
+        else {
+            return (Config::default(), None);
+        };
+
+        match Config::load(&dir) {
+            Ok(config) => (config, None),
+            Err(e) => (Config::default(), Some(e)),
+        }
+    })
+}
+
+/// Define the entry points of a capture library for `$backend`, which call
+/// [`init`] when the library is loaded.
+///
+/// ```ignore
+/// tts_air_capture::entry_point!(tts_air_capture::Backend::Saapi);
+/// ```
+#[macro_export]
+macro_rules! entry_point {
+    ($backend:expr) => {
+        #[cfg(windows)]
+        #[no_mangle]
+        unsafe extern "system" fn DllMain(
+            hwnd: isize,
+            reason: u32,
+            _: *const (),
+        ) {
+            match reason {
+                // init leaks a reference of self to prevent the dll from being
+                // unloaded to simplify cleanup (by not doing it)
+                0/*DLL_PROCESS_DETACH*/ => (),
+
+                1/*DLL_PROCESS_ATTACH*/ => $crate::init(hwnd, $backend),
+
+                _ => (),
+            }
+        }
+
+        // there is no DllMain outside of Windows so run init when the library is loaded
+        #[cfg(all(target_os = "linux", not(test)))]
+        #[used]
+        #[link_section = ".init_array"]
+        static INIT_ARRAY: unsafe extern "C" fn() = {
+            unsafe extern "C" fn init_array() {
+                $crate::init(0, $backend);
+            }
+            init_array
+        };
+    };
+}
+
+/// Start capturing for the library loaded at module handle `hwnd`.
+///
+/// Needs to be called from DllMain on Windows or when the library is loaded
+/// elsewhere.
+///
+/// # Safety
+/// `hwnd` needs to be the module handle of the calling library or 0.
+pub unsafe fn init(hwnd: isize, backend: Backend) {
+    MODULE.store(hwnd, Ordering::Relaxed);
+
+    std::panic::set_hook(Box::new(|e| {
+        log::debug!("err: {e:?}");
+    }));
+
+    if let Ok(mut pump) = PUMP.lock() {
+        if pump.is_none() {
+            let (send, recv) = mpsc::channel();
+            *pump = Some(self::pump(send.clone()));
+            drop(pump);
+
+            thread::spawn(move || {
+                sys::keep_loaded(hwnd);
+
+                let (config, error) = load_config();
+                let level = config.log_level();
+                if level != log::LevelFilter::Off {
+                    if let Ok(fd) = std::fs::OpenOptions::new()
+                        .create(true)
+                        .write(true)
+                        .truncate(true)
+                        .open(&config.log.path)
+                    {
+                        // filter with the global level only so it can be
+                        // raised later
+                        let _ = env_logger::builder()
+                            .target(env_logger::fmt::Target::Pipe(Box::new(fd)))
+                            .filter(None, log::LevelFilter::Trace)
+                            .try_init();
+                        log::set_max_level(level);
+                    }
+                } else {
+                    LOGGING_OFF.store(true, Ordering::Relaxed);
+                }
+
+                if let Some(e) = error {
+                    log::error!("using default settings due to invalid {}: {e}", config::FILE_NAME);
+                }
+
+                spawn_servers::<tts_air_ipc::PipeListener>(config, backend, send, recv);
+                log::debug!("successfully started tts-air-capture");
+            });
+        }
+    }
+}
+
+/// Start capturing with `config` instead of the config file.
+///
+/// Logging is left to the caller. Returns false if capture already started.
+pub fn start(config: &Config, backend: Backend) -> bool {
+    start_with::<tts_air_ipc::PipeListener>(config, backend)
+}
+
+/// Like [`start`] but accepting clients from `L`, such as
+/// [`LoopbackListener`](tts_air_ipc::loopback::LoopbackListener) in tests.
+pub fn start_with<L>(config: &Config, backend: Backend) -> bool
+where
+    L: Listener,
+    L::Transport: 'static,
+{
+    let Ok(mut pump) = PUMP.lock() else {
+        return false;
+    };
+    if pump.is_some() {
+        return false;
+    }
+
+    let (send, recv) = mpsc::channel();
+    *pump = Some(self::pump(send.clone()));
+    drop(pump);
+
+    spawn_servers::<L>(config, backend, send, recv);
+    true
+}
+
+fn spawn_servers<L>(
+    config: &Config,
+    backend: Backend,
+    send: Sender<Input<L::Transport>>,
+    recv: Receiver<Input<L::Transport>>,
+) where
+    L: Listener,
+    L::Transport: 'static,
+{
+    let coalescer = Coalescer::new(config.window(), config.max_batch(), config.max_delay());
+    let history = History::new(config.history);
+    let clients = config.clients.clone();
+    let messages = config.messages.clone();
+    let address = config.address(backend);
+
+    thread::spawn(move || server_broadcast(recv, backend, coalescer, history, clients, messages));
+    thread::spawn(move || server_listen::<L>(&address, send));
+}
+
+fn server_broadcast<T: Transport + 'static>(
+    recv: Receiver<Input<T>>,
+    backend: Backend,
+    mut coalescer: Coalescer,
+    mut history: History<Arc<Encoded>>,
+    clients: config::Clients,
+    messages: config::Messages,
+) {
+    let recv = recv;
+    let mut pipes: Vec<Client> = Vec::new();
+
+    let mut seq = 0;
+    let mut text = String::new();
+    let mut outgoing = Vec::new();
+    loop {
+        // only wake up early for the pending message
+        let input = match coalescer.deadline() {
+            Some(deadline) => match recv.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(input) => Some(input),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(e) => {
+                    log::error!("text pump had error {e:?}");
+                    break;
+                }
+            },
+            None => match recv.recv() {
+                Ok(input) => Some(input),
+                Err(e) => {
+                    log::error!("text pump had error {e:?}");
+                    break;
+                }
+            },
+        };
+
+        match input {
+            Some(Input::Connect(client_id, pipe, replay)) => {
+                // queue the replay first so the client sees no gap or duplicate
+                let initial = history.replay(replay).cloned().collect::<Vec<_>>();
+                log::debug!("client #{client_id} connected, replaying {} messages for {replay:?}", initial.len());
+                pipes.push(Client::spawn(client_id, pipe, initial, clients.queue, clients.overflow));
+            }
+            Some(Input::Request(client_id, id, command)) => {
+                let Some(client) = pipes.iter().find(|client| client.id() == client_id) else {
+                    log::debug!("dropping request #{id} of disconnected client #{client_id}");
+                    continue;
+                };
+
+                let response = match command {
+                    Ok(Command::Replay(replay)) => {
+                        // flagged so the client can tell them from live
+                        // messages, and queued like the answer so none are
+                        // dropped for overflowing the queue
+                        let messages = history.replay(replay).map(|m| Arc::new(m.replayed())).collect::<Vec<_>>();
+                        let count = messages.len() as u64;
+                        client.push_requested(messages);
+                        Response::Replayed(count)
+                    }
+                    Ok(Command::Status) => Response::Status(pipes.iter().map(Client::status).collect()),
+                    Ok(command) => run_command(command, &mut coalescer),
+                    Err(e) => Response::Error(e.to_string()),
+                };
+                log::debug!("client #{client_id} request #{id}: {response:?}");
+
+                let bytes = response.to_frame(id).with_backend(backend).to_bytes();
+                client.push_requested([Arc::new(Encoded { bytes, frames: 1 })]);
+            }
+            Some(Input::Event(Event::Say(wchar), at)) => {
+                text.clear();
+                decode_utf16(&wchar, &mut text);
+                outgoing.extend(coalescer.push(&text, at).map(|m| (Kind::Text, m)));
+            }
+            Some(Input::Event(Event::Braille(wchar), at)) => {
+                // keep events in capture order
+                outgoing.extend(coalescer.flush().map(|m| (Kind::Text, m)));
+
+                let mut text = String::new();
+                decode_utf16(&wchar, &mut text);
+                outgoing.push((Kind::Braille, Message::new(text, at)));
+            }
+            Some(Input::Event(Event::Stop, at)) => {
+                outgoing.extend(coalescer.flush().map(|m| (Kind::Text, m)));
+                outgoing.push((Kind::Stop, Message::new(String::new(), at)));
+            }
+            None => (),
+        }
+        outgoing.extend(coalescer.poll(Instant::now()).map(|m| (Kind::Text, m)));
+
+        for (kind, message) in outgoing.drain(..) {
+            seq += 1;
+            let header = Frame::new(kind, seq, 0, Vec::new()).with_backend(backend);
+            let message = Arc::new(encode(header, message, &messages));
+            pipes.retain(|client| {
+                let connected = client.push(&message);
+                if !connected {
+                    log::info!("client #{} disconnected after {} dropped frames", client.id(), client.dropped());
+                }
+                connected
+            });
+            history.push(seq, message);
+        }
+    }
+}
+
+/// Answer every command except [`Command::Replay`] and [`Command::Status`]
+/// which need the clients.
+fn run_command(command: Command, coalescer: &mut Coalescer) -> Response {
+    match command {
+        Command::Ping => Response::Pong,
+        Command::Version => {
+            let process = std::env::current_exe()
+                .ok()
+                .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+                .unwrap_or_default();
+            Response::Version {
+                version: NAME.to_string(),
+                process,
+            }
+        }
+        Command::SetCoalesce { window_ms, max_delay_ms } => {
+            if window_ms.max(max_delay_ms) > config::MAX_COALESCE_MS {
+                return Response::Error(format!("coalescing exceeds {} ms", config::MAX_COALESCE_MS));
+            }
+
+            coalescer.set_window(Duration::from_millis(window_ms), Duration::from_millis(max_delay_ms));
+            Response::Done
+        }
+        Command::SetLogLevel(level) => {
+            if LOGGING_OFF.load(Ordering::Relaxed) {
+                return Response::Error(format!("logging is turned off in {}", config::FILE_NAME));
+            }
+
+            log::set_max_level(level);
+            Response::Done
+        }
+        Command::Replay(_) => Response::Error("replay needs a client".to_string()),
+        Command::Status => Response::Error("status needs the clients".to_string()),
+    }
+}
+
+/// Encode `message` with the kind, sequence id and backend of `header`.
+fn encode(
+    mut header: Frame,
+    mut message: Message,
+    limits: &config::Messages,
+) -> Encoded {
+    let buffer = &mut message.text;
+    if buffer.len() > limits.max_size {
+        let mut len = limits.max_size;
+        while !buffer.is_char_boundary(len) {
+            len -= 1;
+        }
+        log::warn!("truncating message of {} bytes to {len} bytes", buffer.len());
+        buffer.truncate(len);
+    }
+
+    let (kind, seq) = (header.kind, header.seq);
+    header.timestamp = protocol::timestamp(SystemTime::now() - message.start.elapsed());
+    header.payload = buffer.as_bytes().to_vec();
+    let mut frame = Vec::new();
+    let frames = protocol::encode_chunked(&header, limits.chunk_size, &mut frame);
+    if frames > 1 {
+        let total = SPLIT_MESSAGES.fetch_add(1, Ordering::Relaxed) + 1;
+        log::info!("split message #{seq} of {} bytes into {frames} frames ({total} split so far)", buffer.len());
+    }
+
+    log::debug!("{kind:?} #{seq}: {:?}", buffer);
+    Encoded {
+        bytes: frame,
+        frames,
+    }
+}
+
+fn decode_utf16(wchar: &[u16], out: &mut String) {
+    for c in char::decode_utf16(wchar.iter().copied()) {
+        out.push(c.unwrap_or('\u{FFFD}'));
+    }
+}
+
+fn server_listen<L>(
+    address: &str,
+    pipe_send: Sender<Input<L::Transport>>,
+) where
+    L: Listener,
+    L::Transport: 'static,
+{
+    let pipe_send = pipe_send;
+
+    let mut client_id = 0;
+    let mut pipe = None;
+    loop {
+        if pipe.is_none() {
+            match L::bind(address) {
+                Ok(p) => pipe = Some(p),
+                Err(e) => {
+                    log::error!("failed to listen on {address:?} with error {e}");
+                    thread::sleep(std::time::Duration::from_millis(50));
+                    continue;
+                }
+            };
+        }
+
+        if let Some(mut p) = pipe.take() {
+            match p.listen() {
+                Ok(p) => {
+                    // a client that never says hello only holds up its own
+                    // thread instead of every later connection
+                    client_id += 1;
+                    let send = pipe_send.clone();
+                    let id = client_id;
+                    thread::spawn(move || server_client(id, p, send, HANDSHAKE_TIMEOUT));
+                }
+                Err(e) => {
+                    drop(p);
+                    log::error!("pipe listener had unexpected error {e:?}");
+                    // reduce potential log spam
+                    thread::sleep(Duration::from_millis(500));
+                    continue;
+                }
+            };
+        }
+    }
+}
+
+/// Time a client has to finish the handshake before it is disconnected.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Finish the handshake with client `id` and hand it to the broadcast thread.
+fn server_client<T: Transport + 'static>(
+    id: u64,
+    mut pipe: T,
+    send: Sender<Input<T>>,
+    timeout: Duration,
+) {
+    // a client that never says hello would otherwise hold this thread and
+    // its pipe forever
+    let (done, watch) = mpsc::channel::<()>();
+    match pipe.try_clone() {
+        Ok(handle) => {
+            thread::spawn(move || {
+                if watch.recv_timeout(timeout) == Err(mpsc::RecvTimeoutError::Timeout) {
+                    log::warn!("disconnecting client #{id} for not finishing the handshake in {timeout:?}");
+                    let _ = handle.shutdown();
+                }
+            });
+        }
+        Err(e) => {
+            log::error!("failed to clone client pipe with error {e}");
+            return;
+        }
+    }
+
+    let mut decoder = protocol::Decoder::new();
+    let hello = protocol::handshake_server(&mut pipe, &mut decoder, NAME);
+    drop(done);
+    let replay = match hello {
+        Ok(hello) => {
+            log::debug!("handshake with client {:?} requesting {:?}", hello.name, hello.replay);
+            hello.replay
+        }
+        Err(e) => {
+            log::error!("client handshake failed: {e}");
+            return;
+        }
+    };
+
+    let reader = match pipe.try_clone() {
+        Ok(reader) => reader,
+        Err(e) => {
+            log::error!("failed to clone client pipe with error {e}");
+            return;
+        }
+    };
+
+    if let Err(e) = send.send(Input::Connect(id, pipe, replay)) {
+        log::error!("pipe_send had error {e:?}");
+        return;
+    }
+    log::info!("client connection established");
+    server_read(id, reader, decoder, send);
+}
+
+/// Forward requests of client `id` to the broadcast thread until it
+/// disconnects.
+fn server_read<T: Transport>(
+    id: u64,
+    mut pipe: T,
+    mut decoder: protocol::Decoder,
+    send: Sender<Input<T>>,
+) {
+    let mut buffer = [0; 0x400];
+    loop {
+        let frame = match protocol::recv_frame(&mut pipe, &mut decoder, &mut buffer) {
+            Ok(frame) => frame,
+            Err(ProtocolError::Transport(e)) => {
+                log::debug!("stopped reading from client #{id}: {e}");
+                return;
+            }
+            Err(e) => {
+                log::error!("disconnecting client #{id} for invalid frame: {e}");
+                let _ = pipe.shutdown();
+                return;
+            }
+        };
+
+        if frame.kind != Kind::Request {
+            log::warn!("ignoring {:?} frame from client #{id}", frame.kind);
+            continue;
+        }
+
+        let command = Command::parse(&frame.payload);
+        if send.send(Input::Request(id, frame.seq, command)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Run `f` unless it panicked before to avoid log spam.
+pub fn catch_panic(did_panic: &AtomicBool, f: impl FnOnce() + std::panic::UnwindSafe) {
+    if !did_panic.load(Ordering::SeqCst) && std::panic::catch_unwind(f).is_err() {
+        did_panic.store(true, Ordering::SeqCst);
+        log::debug!("failed to send tts event due to panic");
+    }
+}
+
+/// Queue `wchar` for broadcast as `event`.
+///
+/// # Safety
+/// `wchar` needs to be NULL or a NUL-terminated UTF-16 string.
+pub unsafe fn send_wchar(wchar: *const u16, event: fn(Box<[u16]>) -> Event) {
+    if let Some(text) = box_wchar(wchar) {
+        send_event(event(text));
+    }
+}
+
+pub fn send_event(event: Event) {
+    if let Ok(pump) = PUMP.lock() {
+        if let Some(pump) = &*pump {
+            if !pump(event, Instant::now()) {
+                log::error!("failed to send event over PUMP");
+            }
+        }
+    }
+}
+
+/// Copy a NUL-terminated UTF-16 string.
+///
+/// # Safety
+/// `text` needs to be NULL or a NUL-terminated UTF-16 string.
+pub unsafe fn box_wchar(text: *const u16) -> Option<Box<[u16]>> {
+    if !text.is_null() {
+        let mut len = 0;
+        while *text.wrapping_offset(len) != 0 {
+            len += 1
+        }
+        let slice = core::slice::from_raw_parts(text, len as usize);
+        Some(Box::from(slice))
+    } else {
+        None
+    }
+}
+#[cfg(windows)]
+mod sys {
+    use std::os::windows::ffi::OsStringExt;
+    use std::path::PathBuf;
+
+    pub fn module_path(hwnd: isize) -> Option<PathBuf> {
+        let mut buffer = [0; 0x1000];
+        let len = unsafe {
+            windows_sys::Win32::System::LibraryLoader::GetModuleFileNameW(hwnd, buffer.as_mut_ptr(), 0xfff)
+        };
+        (len != 0).then(|| std::ffi::OsString::from_wide(&buffer[..len as usize]).into())
+    }
+
+    /// Leak a reference to the library so it is never unloaded.
+    pub fn keep_loaded(hwnd: isize) {
+        use windows_sys::Win32::System::LibraryLoader::*;
+
+        let mut buffer = [0; 0x1000];
+        unsafe {
+            GetModuleFileNameW(hwnd, buffer.as_mut_ptr(), 0xfff);
+            let _lib = LoadLibraryW(buffer.as_ptr());
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::ffi::OsStringExt;
+    use std::path::PathBuf;
+
+    /// The module handle is unused since there is no DllMain. The library is
+    /// found from the address of this function instead.
+    pub fn module_path(_hwnd: isize) -> Option<PathBuf> {
+        let mut info = unsafe { core::mem::zeroed::<libc::Dl_info>() };
+        let addr = module_path as *const libc::c_void;
+        if unsafe { libc::dladdr(addr, &mut info) } == 0 || info.dli_fname.is_null() {
+            return None;
+        }
+
+        let path = unsafe { std::ffi::CStr::from_ptr(info.dli_fname) };
+        Some(std::ffi::OsStr::from_bytes(path.to_bytes()).into())
+    }
+
+    /// Keep the library mapped after `dlclose` like the leaked reference on
+    /// Windows.
+    pub fn keep_loaded(hwnd: isize) {
+        let Some(path) = module_path(hwnd).and_then(|path| std::ffi::CString::new(path.into_os_string().into_vec()).ok()) else {
+            return;
+        };
+        // the handle is leaked on purpose
+        unsafe {
+            libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_NOLOAD | libc::RTLD_NODELETE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_timeout() {
+        use tts_air_ipc::loopback;
+
+        const ADDRESS: &str = "capture-handshake-timeout-test\0";
+        let mut listener = loopback::LoopbackListener::bind(ADDRESS).unwrap();
+        let mut silent = loopback::LoopbackPipe::open(ADDRESS).unwrap();
+        let pipe = listener.listen().unwrap();
+
+        let (send, recv) = mpsc::channel();
+        let client = thread::spawn(move || server_client(1, pipe, send, Duration::from_millis(10)));
+        client.join().unwrap();
+        assert!(recv.try_recv().is_err());
+        let mut buffer = [0; 0x400];
+        assert_eq!(silent.recv(&mut buffer), Err(tts_air_ipc::IpcError::Disconnected));
+    }
+}
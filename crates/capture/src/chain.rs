@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Environment variable with the path of a SAAPI library to forward calls to.
+///
+/// Takes precedence over `downstream` in the config file.
+pub const DOWNSTREAM_ENV: &str = "TTS_AIR_DOWNSTREAM";
+
+/// Screen reader that receives calls after they are captured.
+pub trait Saapi: Send + Sync {
+    unsafe fn say(&self, wchar: *const u16) -> u8;
+    unsafe fn braille(&self, wchar: *const u16) -> u8;
+    unsafe fn stop(&self) -> u8;
+    unsafe fn is_running(&self) -> u8;
+}
+
+static DOWNSTREAM: OnceLock<Option<Box<dyn Saapi>>> = OnceLock::new();
+
+/// Use `downstream` instead of the configured library.
+///
+/// Fails if a downstream was already resolved.
+#[cfg(test)]
+pub fn set(downstream: Option<Box<dyn Saapi>>) -> bool {
+    DOWNSTREAM.set(downstream).is_ok()
+}
+
+/// Call `f` with the downstream library if there is one.
+///
+/// Loading is deferred to the first call since it can't happen in DllMain.
+pub fn forward(f: impl FnOnce(&dyn Saapi) -> u8) -> Option<u8> {
+    let downstream = DOWNSTREAM.get_or_init(|| {
+        let path = std::env::var_os(DOWNSTREAM_ENV)
+            .map(PathBuf::from)
+            .or_else(|| tts_air_capture::config().downstream.clone())?;
+        load(&path)
+    });
+
+    downstream.as_deref().map(f)
+}
+
+fn load(path: &Path) -> Option<Box<dyn Saapi>> {
+    match unsafe { Library::open(path) } {
+        Ok(lib) => {
+            log::info!("forwarding screen reader calls to {path:?}");
+            Some(Box::new(lib))
+        }
+        Err(e) => {
+            log::error!("failed to load downstream screen reader {path:?}: {e}");
+            None
+        }
+    }
+}
+
+type WcharFn = unsafe extern "C" fn(*const u16) -> u8;
+type VoidFn = unsafe extern "C" fn() -> u8;
+
+/// SAAPI exports of a dynamically loaded library.
+struct Library {
+    say: Option<WcharFn>,
+    braille: Option<WcharFn>,
+    stop: Option<VoidFn>,
+    is_running: Option<VoidFn>,
+}
+
+impl Library {
+    /// The library is never unloaded.
+    unsafe fn open(path: &Path) -> Result<Self, String> {
+        let lib = sys::open(path).ok_or_else(|| "library not found".to_string())?;
+        let say = sys::symbol(lib, "SA_SayW\0")
+            .map(|f| core::mem::transmute::<*const (), WcharFn>(f));
+
+        // forwarding to ourselves would recurse forever
+        if say.map(|f| f as usize) == Some(crate::saapi::SA_SayW as WcharFn as usize) {
+            return Err("library is tts-air itself".to_string());
+        }
+
+        Ok(Self {
+            say,
+            braille: sys::symbol(lib, "SA_BrlShowTextW\0")
+                .map(|f| core::mem::transmute::<*const (), WcharFn>(f)),
+            stop: sys::symbol(lib, "SA_StopAudio\0")
+                .map(|f| core::mem::transmute::<*const (), VoidFn>(f)),
+            is_running: sys::symbol(lib, "SA_IsRunning\0")
+                .map(|f| core::mem::transmute::<*const (), VoidFn>(f)),
+        })
+    }
+}
+
+impl Saapi for Library {
+    unsafe fn say(&self, wchar: *const u16) -> u8 {
+        self.say.map_or(0, |f| f(wchar))
+    }
+
+    unsafe fn braille(&self, wchar: *const u16) -> u8 {
+        self.braille.map_or(0, |f| f(wchar))
+    }
+
+    unsafe fn stop(&self) -> u8 {
+        self.stop.map_or(0, |f| f())
+    }
+
+    unsafe fn is_running(&self) -> u8 {
+        self.is_running.map_or(0, |f| f())
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    use windows_sys::Win32::System::LibraryLoader::*;
+
+    pub unsafe fn open(path: &Path) -> Option<isize> {
+        let path = path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+        let lib = LoadLibraryW(path.as_ptr());
+        (lib != 0).then_some(lib)
+    }
+
+    pub unsafe fn symbol(lib: isize, name: &str) -> Option<*const ()> {
+        GetProcAddress(lib, name.as_ptr()).map(|f| f as *const ())
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    pub unsafe fn open(path: &Path) -> Option<isize> {
+        let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+        let lib = libc::dlopen(path.as_ptr(), libc::RTLD_NOW);
+        (!lib.is_null()).then_some(lib as isize)
+    }
+
+    pub unsafe fn symbol(lib: isize, name: &str) -> Option<*const ()> {
+        let f = libc::dlsym(lib as *mut _, name.as_ptr() as *const _);
+        (!f.is_null()).then_some(f as *const ())
+    }
+}
@@ -1,33 +1,97 @@
 use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering;
+
+use tts_air_capture::Event;
+use tts_air_capture::catch_panic;
+
+use crate::chain;
 
 #[no_mangle]
-unsafe extern "C" fn SA_SayW(wchar: *const u16) -> u8 {
+pub(crate) unsafe extern "C" fn SA_SayW(wchar: *const u16) -> u8 {
     static DID_PANIC: AtomicBool = AtomicBool::new(false);
-
-    // avoid log spam if a panic happens
-    if !DID_PANIC.load(Ordering::SeqCst) {
-        if let Err(_) = std::panic::catch_unwind(|| {
-            crate::send_wchar(wchar);
-        }) {
-            DID_PANIC.store(true, Ordering::SeqCst);
-            log::debug!("failed to send tts event due to panic");
-        }
-    }
-    true.into()
+    catch_panic(&DID_PANIC, || tts_air_capture::send_wchar(wchar, Event::Say));
+    chain::forward(|d| d.say(wchar)).unwrap_or(true.into())
 }
 
 #[no_mangle]
-unsafe extern "C" fn SA_BrlShowTextW(_wchar: *const u16) -> u8 {
-    true.into()
+pub(crate) unsafe extern "C" fn SA_BrlShowTextW(wchar: *const u16) -> u8 {
+    static DID_PANIC: AtomicBool = AtomicBool::new(false);
+    catch_panic(&DID_PANIC, || tts_air_capture::send_wchar(wchar, Event::Braille));
+    chain::forward(|d| d.braille(wchar)).unwrap_or(true.into())
 }
 
 #[no_mangle]
-unsafe extern "C" fn SA_StopAudio() -> u8 {
-    true.into()
+pub(crate) unsafe extern "C" fn SA_StopAudio() -> u8 {
+    static DID_PANIC: AtomicBool = AtomicBool::new(false);
+    catch_panic(&DID_PANIC, || tts_air_capture::send_event(Event::Stop));
+    chain::forward(|d| d.stop()).unwrap_or(true.into())
 }
 
 #[no_mangle]
-unsafe extern "C" fn SA_IsRunning() -> u8 {
-    true.into()
+pub(crate) unsafe extern "C" fn SA_IsRunning() -> u8 {
+    chain::forward(|d| d.is_running()).unwrap_or(true.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Fake {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Fake {
+        unsafe fn push(&self, call: &str, wchar: *const u16) {
+            let text = tts_air_capture::box_wchar(wchar).unwrap_or_default();
+            self.calls.lock().unwrap().push(format!("{call} {}", String::from_utf16_lossy(&text)));
+        }
+    }
+
+    impl chain::Saapi for &'static Fake {
+        unsafe fn say(&self, wchar: *const u16) -> u8 {
+            self.push("say", wchar);
+            2
+        }
+
+        unsafe fn braille(&self, wchar: *const u16) -> u8 {
+            self.push("braille", wchar);
+            3
+        }
+
+        unsafe fn stop(&self) -> u8 {
+            self.push("stop", core::ptr::null());
+            4
+        }
+
+        unsafe fn is_running(&self) -> u8 {
+            self.push("is_running", core::ptr::null());
+            0
+        }
+    }
+
+    fn wchar(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    #[test]
+    fn forwards_to_downstream() {
+        let fake: &'static Fake = Box::leak(Box::default());
+        assert!(chain::set(Some(Box::new(fake))));
+
+        unsafe {
+            assert_eq!(SA_IsRunning(), 0);
+            assert_eq!(SA_SayW(wchar("hello").as_ptr()), 2);
+            assert_eq!(SA_BrlShowTextW(wchar("world").as_ptr()), 3);
+            assert_eq!(SA_StopAudio(), 4);
+        }
+
+        assert_eq!(*fake.calls.lock().unwrap(), [
+            "is_running ",
+            "say hello",
+            "braille world",
+            "stop ",
+        ]);
+    }
 }
\ No newline at end of file
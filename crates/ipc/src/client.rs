@@ -0,0 +1,489 @@
+//! Blocking client for the events sent by the capture library.
+//!
+//! ```no_run
+//! use tts_air_ipc::client::TtsClient;
+//!
+//! // connects to the first running game found by `discover`
+//! let client = TtsClient::new("my-tool")
+//!     .with_replay_last(1)
+//!     .on_state(|state| println!("{state:?}"));
+//! for event in client {
+//!     println!("#{} {:?}", event.seq, event.kind);
+//! }
+//! ```
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::IpcError;
+use crate::Pipe;
+use crate::Transport;
+use crate::control::Command;
+use crate::control::Response;
+use crate::protocol;
+use crate::protocol::Backend;
+use crate::protocol::Decoder;
+use crate::protocol::Frame;
+use crate::protocol::Hello;
+use crate::protocol::Kind;
+use crate::protocol::ProtocolError;
+use crate::protocol::Reassembler;
+use crate::protocol::Replay;
+
+/// Delay before the first reconnect attempt, doubled after every failed
+/// attempt up to [`MAX_BACKOFF`].
+pub const MIN_BACKOFF: Duration = Duration::from_millis(100);
+pub const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Message captured from a screen reader API call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TtsEvent {
+    pub kind: TtsEventKind,
+    pub backend: Backend,
+    pub seq: u64,
+    /// Microseconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Sent again for a [`Command::Replay`] after it was sent live.
+    pub replayed: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TtsEventKind {
+    Message(String),
+    Braille(String),
+    Stop,
+}
+
+impl TtsEvent {
+    /// Returns `None` for frames that are not events.
+    pub fn from_frame(frame: protocol::Frame) -> Option<Self> {
+        let text = || String::from_utf8_lossy(&frame.payload).into_owned();
+        let kind = match frame.kind {
+            Kind::Text => TtsEventKind::Message(text()),
+            Kind::Braille => TtsEventKind::Braille(text()),
+            Kind::Stop => TtsEventKind::Stop,
+            Kind::Hello | Kind::Continuation | Kind::Request | Kind::Response => return None,
+        };
+
+        Some(Self {
+            kind,
+            backend: frame.backend,
+            seq: frame.seq,
+            timestamp: frame.timestamp,
+            replayed: frame.replayed,
+        })
+    }
+}
+
+/// Passed to the [`TtsClient::on_state`] callback.
+#[derive(Debug)]
+pub enum ConnectionState<'a> {
+    /// Handshake finished with the capture library that sent `hello`.
+    Connected(&'a Hello),
+    /// Connection to the capture library was lost.
+    Disconnected(ProtocolError),
+}
+
+type StateCallback = Box<dyn FnMut(&ConnectionState) + Send>;
+type Writer = Box<dyn FnMut(&Frame) -> Result<(), ProtocolError> + Send>;
+
+/// Sends [`Command`]s over the current connection of a [`TtsClient`].
+///
+/// The responses are only received while the client is being iterated.
+#[derive(Clone, Default)]
+pub struct Controller {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    writer: Mutex<Option<Writer>>,
+    /// Requests waiting for their response by request id.
+    pending: Mutex<HashMap<u64, mpsc::Sender<Response>>>,
+    next_id: AtomicU64,
+}
+
+impl Controller {
+    /// Send `command` and wait up to `timeout` for its response.
+    ///
+    /// Fails with [`IpcError::Disconnected`] if the client is not connected
+    /// or disconnects before the response arrives.
+    pub fn request(&self, command: &Command, timeout: Duration) -> Result<Response, ProtocolError> {
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (send, recv) = mpsc::channel();
+        self.shared.pending.lock().unwrap().insert(id, send);
+
+        let res = match &mut *self.shared.writer.lock().unwrap() {
+            Some(writer) => writer(&command.to_frame(id)),
+            None => Err(ProtocolError::Transport(IpcError::Disconnected)),
+        };
+
+        let res = res.and_then(|_| match recv.recv_timeout(timeout) {
+            Ok(response) => Ok(response),
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(ProtocolError::Transport(IpcError::Timeout)),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(ProtocolError::Transport(IpcError::Disconnected)),
+        });
+        self.shared.pending.lock().unwrap().remove(&id);
+        res
+    }
+
+    fn respond(&self, id: u64, response: Response) {
+        match self.shared.pending.lock().unwrap().remove(&id) {
+            Some(send) => {
+                let _ = send.send(response);
+            }
+            None => log::debug!("dropping response to unknown or expired request {id}"),
+        }
+    }
+
+    fn connect(&self, writer: Option<Writer>) {
+        *self.shared.writer.lock().unwrap() = writer;
+    }
+
+    fn disconnect(&self) {
+        *self.shared.writer.lock().unwrap() = None;
+        // wakes every waiting request
+        self.shared.pending.lock().unwrap().clear();
+    }
+}
+
+struct Connection<T> {
+    pipe: T,
+    decoder: Decoder,
+    reassembler: Reassembler,
+}
+
+/// Yields events from the capture library, reconnecting whenever the
+/// connection is lost.
+///
+/// Messages sent while disconnected are replayed on reconnect as far as the
+/// capture library's history reaches. Iteration only ends once
+/// [`TtsClient::with_retry_limit`] is reached.
+pub struct TtsClient<T: Transport = Pipe> {
+    name: String,
+    /// Address given with [`TtsClient::with_address`].
+    address: Option<String>,
+    /// Address found by [`discover`](crate::discover) otherwise, kept until
+    /// connecting to it fails.
+    discovered: Option<String>,
+    replay_last: u64,
+    on_state: Option<StateCallback>,
+    connection: Option<Connection<T>>,
+    /// Last event received so a reconnect picks up where it left off.
+    last_seq: Option<u64>,
+    backoff: Duration,
+    controller: Controller,
+    retry_limit: Option<u32>,
+    /// Connect attempts that failed since the last successful one.
+    failures: u32,
+    buffer: Box<[u8]>,
+}
+
+impl TtsClient<Pipe> {
+    /// Client that sends `name` in its hello.
+    ///
+    /// Connects to the capture library with the lowest process id found by
+    /// [`discover`](crate::discover) unless given an address.
+    pub fn new(name: &str) -> Self {
+        Self::with_transport(name)
+    }
+}
+
+impl<T: Transport + 'static> TtsClient<T> {
+    /// Like [`TtsClient::new`] over a different [`Transport`].
+    pub fn with_transport(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            address: None,
+            discovered: None,
+            replay_last: 0,
+            on_state: None,
+            connection: None,
+            last_seq: None,
+            backoff: MIN_BACKOFF,
+            controller: Controller::default(),
+            retry_limit: None,
+            failures: 0,
+            buffer: vec![0; 0x10000].into_boxed_slice(),
+        }
+    }
+
+    /// Connect to a NUL-terminated `address` instead.
+    pub fn with_address(mut self, address: &str) -> Self {
+        self.address = Some(address.to_string());
+        self
+    }
+
+    /// Ask for the `count` most recent messages on the first connection.
+    pub fn with_replay_last(mut self, count: u64) -> Self {
+        self.replay_last = count;
+        self
+    }
+
+    /// Give up after `attempts` connect attempts in a row failed, such as
+    /// when the capture library's process exited.
+    pub fn with_retry_limit(mut self, attempts: u32) -> Self {
+        self.retry_limit = Some(attempts);
+        self
+    }
+
+    /// Call `f` whenever the client connects or disconnects.
+    pub fn on_state(mut self, f: impl FnMut(&ConnectionState) + Send + 'static) -> Self {
+        self.on_state = Some(Box::new(f));
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Handle for sending commands while another thread iterates the client.
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+
+    /// Block until the next event, reconnecting as needed.
+    ///
+    /// Returns `None` once the retry limit is reached.
+    pub fn recv(&mut self) -> Option<TtsEvent> {
+        loop {
+            let connection = match &mut self.connection {
+                Some(connection) => connection,
+                None => match self.connect() {
+                    Ok(connection) => self.connection.insert(connection),
+                    Err(e) => {
+                        log::trace!("failed connect to tts capture: {e}");
+                        self.failures += 1;
+                        if self.retry_limit.is_some_and(|limit| self.failures >= limit) {
+                            log::debug!("giving up on {:?} after {} attempts", self.address.as_ref().or(self.discovered.as_ref()), self.failures);
+                            return None;
+                        }
+                        thread::sleep(self.backoff);
+                        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                        continue;
+                    }
+                },
+            };
+
+            let frame = match protocol::recv_frame(&mut connection.pipe, &mut connection.decoder, &mut self.buffer) {
+                Ok(frame) => connection.reassembler.push(frame),
+                Err(e) => {
+                    self.disconnect(e);
+                    continue;
+                }
+            };
+
+            let Some(frame) = frame else {
+                continue;
+            };
+            if frame.kind == Kind::Response {
+                match Response::parse(&frame.payload) {
+                    Ok(response) => self.controller.respond(frame.seq, response),
+                    Err(e) => log::debug!("ignoring response to request {}: {e}", frame.seq),
+                }
+                continue;
+            }
+            // replays of older messages don't move where a reconnect picks up
+            if !frame.replayed {
+                self.last_seq = Some(self.last_seq.map_or(frame.seq, |seq| seq.max(frame.seq)));
+            }
+
+            match TtsEvent::from_frame(frame) {
+                Some(event) => return Some(event),
+                None => log::debug!("ignoring unexpected frame"),
+            }
+        }
+    }
+
+    fn connect(&mut self) -> Result<Connection<T>, ProtocolError> {
+        let (address, mut pipe) = match &self.address {
+            Some(address) => (address.clone(), T::open(address).map_err(ProtocolError::Transport)?),
+            None => self.open_discovered()?,
+        };
+        let mut decoder = Decoder::new();
+        let replay = match self.last_seq {
+            Some(seq) => Replay::Since(seq),
+            None if self.replay_last > 0 => Replay::Last(self.replay_last),
+            None => Replay::None,
+        };
+
+        let hello = match protocol::handshake_client(&mut pipe, &mut decoder, &self.name, replay) {
+            Ok(hello) => hello,
+            Err(e) => {
+                log::error!("handshake with tts capture failed: {e}");
+                return Err(e);
+            }
+        };
+
+        log::info!("connected to text-to-speech capture {:?} at {:?}", hello.name, address);
+        let writer = match pipe.try_clone() {
+            Ok(mut writer) => Some(Box::new(move |frame: &Frame| protocol::send_frame(&mut writer, frame)) as Writer),
+            Err(e) => {
+                log::warn!("commands unavailable, failed to clone pipe: {e}");
+                None
+            }
+        };
+        self.controller.connect(writer);
+        self.backoff = MIN_BACKOFF;
+        self.failures = 0;
+        if let Some(f) = &mut self.on_state {
+            f(&ConnectionState::Connected(&hello));
+        }
+
+        Ok(Connection {
+            pipe,
+            decoder,
+            reassembler: Reassembler::new(),
+        })
+    }
+
+    /// Open the game connected to before, or else the first one found that
+    /// accepts the connection since stale addresses can be found too.
+    fn open_discovered(&mut self) -> Result<(String, T), ProtocolError> {
+        if let Some(address) = &self.discovered {
+            match T::open(address) {
+                Ok(pipe) => return Ok((address.clone(), pipe)),
+                Err(e) => {
+                    // look for another game next time, whose sequence ids
+                    // have nothing to do with the ones seen so far
+                    self.discovered = None;
+                    self.last_seq = None;
+                    return Err(ProtocolError::Transport(e));
+                }
+            }
+        }
+
+        for (_, _, address) in crate::discover::<T>() {
+            if let Ok(pipe) = T::open(&address) {
+                self.discovered = Some(address.clone());
+                return Ok((address, pipe));
+            }
+        }
+        Err(ProtocolError::Transport(IpcError::Other(std::io::ErrorKind::NotFound)))
+    }
+
+    fn disconnect(&mut self, e: ProtocolError) {
+        match &e {
+            ProtocolError::Transport(IpcError::Disconnected) => {
+                log::info!("text-to-speech capture disconnected");
+            }
+            e => log::trace!("failed connection read with error {e}"),
+        }
+
+        self.connection = None;
+        self.controller.disconnect();
+        if let Some(f) = &mut self.on_state {
+            f(&ConnectionState::Disconnected(e));
+        }
+    }
+}
+
+impl<T: Transport + 'static> Iterator for TtsClient<T> {
+    type Item = TtsEvent;
+
+    fn next(&mut self) -> Option<TtsEvent> {
+        self.recv()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::Listener;
+    use crate::PipeListener;
+    use crate::protocol::Frame;
+
+    #[test]
+    fn reconnects_and_replays() {
+        let address = format!("/tmp/net.wartide.d4.tts-air-test-client-{}\0", std::process::id());
+        let mut listener = PipeListener::bind(&address).unwrap();
+
+        let (send, recv) = mpsc::channel();
+        let mut client = TtsClient::new("test")
+            .with_address(&address)
+            .with_replay_last(3)
+            .on_state(move |state| {
+                let state = match state {
+                    ConnectionState::Connected(hello) => Ok(hello.name.clone()),
+                    ConnectionState::Disconnected(e) => Err(format!("{e}")),
+                };
+                send.send(state).unwrap();
+            });
+
+        let server = thread::spawn(move || {
+            let mut replays = Vec::new();
+            for seq in [1, 2, 3] {
+                let mut pipe = listener.listen().unwrap();
+                let mut decoder = Decoder::new();
+                let hello = protocol::handshake_server(&mut pipe, &mut decoder, "server").unwrap();
+                replays.push(hello.replay);
+
+                let frame = Frame::new(Kind::Text, seq, 0, "Ancestral Unique".repeat(seq as usize).into_bytes())
+                    .with_backend(Backend::Nvda);
+                let mut out = Vec::new();
+                protocol::encode_chunked(&frame, 5, &mut out);
+                if seq == 2 {
+                    // a requested replay of the first message
+                    let frame = Frame::new(Kind::Text, 1, 0, b"Ancestral Unique".to_vec()).with_replayed(true);
+                    protocol::encode_chunked(&frame, 5, &mut out);
+                }
+                pipe.send(&out).unwrap();
+
+                listener = PipeListener::bind(&address).unwrap();
+            }
+            replays
+        });
+
+        let event = client.next().unwrap();
+        assert_eq!(event.kind, TtsEventKind::Message("Ancestral Unique".into()));
+        assert_eq!((event.seq, event.backend), (1, Backend::Nvda));
+        assert_eq!(recv.recv().unwrap(), Ok("server".into()));
+
+        let event = client.next().unwrap();
+        assert_eq!(event.kind, TtsEventKind::Message("Ancestral Unique".repeat(2)));
+        assert_eq!(recv.recv().unwrap(), Err("transport error: peer disconnected".into()));
+        assert_eq!(recv.recv().unwrap(), Ok("server".into()));
+        let event = client.next().unwrap();
+        assert_eq!((event.seq, event.replayed), (1, true));
+
+        // the replay didn't move the reconnect back
+        let event = client.next().unwrap();
+        assert_eq!((event.seq, event.replayed), (3, false));
+        assert_eq!(server.join().unwrap(), [Replay::Last(3), Replay::Since(1), Replay::Since(2)]);
+    }
+
+    #[test]
+    fn discovers_by_default() {
+        use crate::loopback;
+
+        // loopback addresses are only found within this process
+        let mut listener = loopback::LoopbackListener::bind(&crate::instance_address(std::process::id(), Backend::Saapi)).unwrap();
+        let server = thread::spawn(move || {
+            let mut pipe = listener.listen().unwrap();
+            let mut decoder = Decoder::new();
+            protocol::handshake_server(&mut pipe, &mut decoder, "server").unwrap();
+            pipe.send(&Frame::new(Kind::Stop, 4, 0, Vec::new()).to_bytes()).unwrap();
+        });
+
+        let mut client = TtsClient::<loopback::LoopbackPipe>::with_transport("test").with_retry_limit(10);
+        let event = client.next().unwrap();
+        assert_eq!((event.seq, event.kind), (4, TtsEventKind::Stop));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn retry_limit() {
+        let address = format!("/tmp/net.wartide.d4.tts-air-test-missing-{}\0", std::process::id());
+        let mut client = TtsClient::new("test")
+            .with_address(&address)
+            .with_retry_limit(2);
+        assert_eq!(client.next(), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! Commands a client sends to the capture library over its connection.
+//!
+//! A [`Kind::Request`] frame carries a [`Command`] and the capture library
+//! answers with a [`Kind::Response`] frame carrying a [`Response`]. The
+//! response keeps the sequence id the client picked for the request and is
+//! queued behind messages already on their way to the client.
+//!
+//! Both payloads start with a tag byte followed by little-endian integers or
+//! UTF-8 strings depending on the tag.
+use std::time::SystemTime;
+
+use crate::protocol::Frame;
+use crate::protocol::Kind;
+use crate::protocol::ProtocolError;
+use crate::protocol::Replay;
+use crate::protocol::timestamp;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Answered with [`Response::Pong`].
+    Ping,
+    /// Answered with [`Response::Version`].
+    Version,
+    /// Change how long text events wait to be merged.
+    SetCoalesce {
+        window_ms: u64,
+        max_delay_ms: u64,
+    },
+    /// Send recent messages again, answered with [`Response::Replayed`] after
+    /// the last of them.
+    Replay(Replay),
+    /// Change the log level of the capture library.
+    SetLogLevel(log::LevelFilter),
+    /// Answered with [`Response::Status`].
+    Status,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    Pong,
+    Version {
+        /// Name and version of the capture library.
+        version: String,
+        /// File name of the host executable.
+        process: String,
+    },
+    /// Command took effect.
+    Done,
+    /// Number of messages sent again.
+    Replayed(u64),
+    /// Command was rejected for the given reason.
+    Error(String),
+    /// Every connected client, ordered by id.
+    Status(Vec<ClientStatus>),
+}
+
+/// Connected client of the capture library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientStatus {
+    /// Numbered from 1 in the order clients connected.
+    pub id: u64,
+    /// Messages waiting to be sent.
+    pub queued: u64,
+    /// Frames dropped because the client fell behind.
+    pub dropped: u64,
+}
+
+fn level_to_u8(level: log::LevelFilter) -> u8 {
+    match level {
+        log::LevelFilter::Off => 0,
+        log::LevelFilter::Error => 1,
+        log::LevelFilter::Warn => 2,
+        log::LevelFilter::Info => 3,
+        log::LevelFilter::Debug => 4,
+        log::LevelFilter::Trace => 5,
+    }
+}
+
+fn level_from_u8(level: u8) -> Option<log::LevelFilter> {
+    log::LevelFilter::iter().nth(level as usize)
+}
+
+fn u64_at(bytes: &[u8], i: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(i..i + 8)?.try_into().ok()?))
+}
+
+impl Command {
+    pub fn parse(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let Some((tag, args)) = payload.split_first() else {
+            return Err(ProtocolError::BadControl);
+        };
+
+        let command = match (tag, args.len()) {
+            (0, 0) => Some(Command::Ping),
+            (1, 0) => Some(Command::Version),
+            (2, 16) => u64_at(args, 0).zip(u64_at(args, 8)).map(|(window_ms, max_delay_ms)| {
+                Command::SetCoalesce { window_ms, max_delay_ms }
+            }),
+            (3, _) => Replay::decode(args).map(Command::Replay),
+            (4, 1) => level_from_u8(args[0]).map(Command::SetLogLevel),
+            (5, 0) => Some(Command::Status),
+            _ => None,
+        };
+
+        command.ok_or(ProtocolError::BadControl)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Command::Ping => out.push(0),
+            Command::Version => out.push(1),
+            Command::SetCoalesce { window_ms, max_delay_ms } => {
+                out.push(2);
+                out.extend_from_slice(&window_ms.to_le_bytes());
+                out.extend_from_slice(&max_delay_ms.to_le_bytes());
+            }
+            Command::Replay(replay) => {
+                out.push(3);
+                replay.encode(&mut out);
+            }
+            Command::SetLogLevel(level) => {
+                out.push(4);
+                out.push(level_to_u8(*level));
+            }
+            Command::Status => out.push(5),
+        }
+        out
+    }
+
+    /// [`Kind::Request`] frame with request id `id`.
+    pub fn to_frame(&self, id: u64) -> Frame {
+        Frame::new(Kind::Request, id, timestamp(SystemTime::now()), self.to_bytes())
+    }
+}
+
+impl Response {
+    pub fn parse(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let Some((tag, args)) = payload.split_first() else {
+            return Err(ProtocolError::BadControl);
+        };
+
+        let text = || String::from_utf8_lossy(args).into_owned();
+        let response = match tag {
+            0 if args.is_empty() => Some(Response::Pong),
+            1 => {
+                let i = args.iter().position(|b| *b == 0).ok_or(ProtocolError::BadControl)?;
+                Some(Response::Version {
+                    version: String::from_utf8_lossy(&args[..i]).into_owned(),
+                    process: String::from_utf8_lossy(&args[i + 1..]).into_owned(),
+                })
+            }
+            2 if args.is_empty() => Some(Response::Done),
+            3 if args.len() == 8 => u64_at(args, 0).map(Response::Replayed),
+            4 => Some(Response::Error(text())),
+            5 if args.len() % 24 == 0 => Some(Response::Status(args.chunks(24)
+                .filter_map(|client| Some(ClientStatus {
+                    id: u64_at(client, 0)?,
+                    queued: u64_at(client, 8)?,
+                    dropped: u64_at(client, 16)?,
+                }))
+                .collect())),
+            _ => None,
+        };
+
+        response.ok_or(ProtocolError::BadControl)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Response::Pong => out.push(0),
+            Response::Version { version, process } => {
+                out.push(1);
+                out.extend_from_slice(version.as_bytes());
+                out.push(0);
+                out.extend_from_slice(process.as_bytes());
+            }
+            Response::Done => out.push(2),
+            Response::Replayed(count) => {
+                out.push(3);
+                out.extend_from_slice(&count.to_le_bytes());
+            }
+            Response::Error(reason) => {
+                out.push(4);
+                out.extend_from_slice(reason.as_bytes());
+            }
+            Response::Status(clients) => {
+                out.push(5);
+                for client in clients {
+                    out.extend_from_slice(&client.id.to_le_bytes());
+                    out.extend_from_slice(&client.queued.to_le_bytes());
+                    out.extend_from_slice(&client.dropped.to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    /// [`Kind::Response`] frame answering request `id`.
+    pub fn to_frame(&self, id: u64) -> Frame {
+        Frame::new(Kind::Response, id, timestamp(SystemTime::now()), self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for command in [
+            Command::Ping,
+            Command::Version,
+            Command::SetCoalesce { window_ms: 5, max_delay_ms: 20 },
+            Command::Replay(Replay::Since(7)),
+            Command::SetLogLevel(log::LevelFilter::Off),
+            Command::SetLogLevel(log::LevelFilter::Trace),
+            Command::Status,
+        ] {
+            assert_eq!(Command::parse(&command.to_bytes()), Ok(command));
+        }
+
+        for response in [
+            Response::Pong,
+            Response::Version { version: "tts-air-capture@1.0.0".into(), process: "Diablo IV.exe".into() },
+            Response::Done,
+            Response::Replayed(3),
+            Response::Error("window_ms exceeds 1000".into()),
+            Response::Status(Vec::new()),
+            Response::Status(vec![
+                ClientStatus { id: 1, queued: 0, dropped: 0 },
+                ClientStatus { id: 3, queued: 64, dropped: 12 },
+            ]),
+        ] {
+            assert_eq!(Response::parse(&response.to_bytes()), Ok(response));
+        }
+
+        assert_eq!(Command::parse(&[]), Err(ProtocolError::BadControl));
+        assert_eq!(Command::parse(&[2, 1]), Err(ProtocolError::BadControl));
+        assert_eq!(Command::parse(&[4, 6]), Err(ProtocolError::BadControl));
+        assert_eq!(Response::parse(&[9]), Err(ProtocolError::BadControl));
+        assert_eq!(Response::parse(&[5, 1]), Err(ProtocolError::BadControl));
+    }
+}
@@ -0,0 +1,123 @@
+use std::io;
+
+/// Error from a [`Listener`](crate::Listener) or [`Transport`](crate::Transport).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpcError {
+    /// Address is empty, too long or not NUL-terminated.
+    InvalidPath,
+    /// Pipe or listener was already closed or consumed.
+    InvalidHandle,
+    /// Peer closed the connection.
+    Disconnected,
+    /// No peer connected before the timeout.
+    Timeout,
+    /// Operation is still in progress.
+    Pending,
+    /// Any other error code from the OS.
+    Os(i32),
+    /// Any other error without an OS error code.
+    Other(io::ErrorKind),
+}
+
+impl IpcError {
+    /// Classify an OS error code from `GetLastError` or `errno`.
+    pub fn from_os(code: i32) -> Self {
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::Foundation::*;
+
+            match code as u32 {
+                ERROR_BROKEN_PIPE | ERROR_NO_DATA | ERROR_PIPE_NOT_CONNECTED => IpcError::Disconnected,
+                ERROR_INVALID_HANDLE => IpcError::InvalidHandle,
+                ERROR_IO_PENDING | ERROR_IO_INCOMPLETE => IpcError::Pending,
+                ERROR_SEM_TIMEOUT | WAIT_TIMEOUT => IpcError::Timeout,
+                _ => IpcError::Os(code),
+            }
+        }
+
+        #[cfg(unix)]
+        {
+            match code {
+                libc::EPIPE | libc::ECONNRESET | libc::ENOTCONN => IpcError::Disconnected,
+                libc::EBADF => IpcError::InvalidHandle,
+                libc::EAGAIN | libc::EINPROGRESS => IpcError::Pending,
+                libc::ETIMEDOUT => IpcError::Timeout,
+                _ => IpcError::Os(code),
+            }
+        }
+    }
+
+    /// The calling thread's last OS error.
+    pub fn last_os_error() -> Self {
+        io::Error::last_os_error().into()
+    }
+}
+
+impl std::fmt::Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpcError::InvalidPath => write!(f, "invalid pipe address"),
+            IpcError::InvalidHandle => write!(f, "pipe is closed"),
+            IpcError::Disconnected => write!(f, "peer disconnected"),
+            IpcError::Timeout => write!(f, "timed out"),
+            IpcError::Pending => write!(f, "operation is pending"),
+            IpcError::Os(code) => write!(f, "{}", io::Error::from_raw_os_error(*code)),
+            IpcError::Other(kind) => write!(f, "{kind}"),
+        }
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+impl From<io::Error> for IpcError {
+    fn from(e: io::Error) -> Self {
+        if let Some(code) = e.raw_os_error() {
+            return Self::from_os(code);
+        }
+
+        match e.kind() {
+            io::ErrorKind::InvalidInput => IpcError::InvalidPath,
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::WriteZero
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::NotConnected => IpcError::Disconnected,
+            io::ErrorKind::TimedOut => IpcError::Timeout,
+            io::ErrorKind::WouldBlock => IpcError::Pending,
+            kind => IpcError::Other(kind),
+        }
+    }
+}
+
+impl From<IpcError> for io::Error {
+    fn from(e: IpcError) -> Self {
+        let kind = match e {
+            IpcError::Os(code) => return io::Error::from_raw_os_error(code),
+            IpcError::Other(kind) => return kind.into(),
+            IpcError::InvalidPath => io::ErrorKind::InvalidInput,
+            IpcError::InvalidHandle => io::ErrorKind::NotConnected,
+            IpcError::Disconnected => io::ErrorKind::BrokenPipe,
+            IpcError::Timeout => io::ErrorKind::TimedOut,
+            IpcError::Pending => io::ErrorKind::WouldBlock,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_conversion() {
+        let e = io::Error::from(IpcError::Disconnected);
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(IpcError::from(io::Error::from(io::ErrorKind::UnexpectedEof)), IpcError::Disconnected);
+        assert_eq!(IpcError::from(io::Error::from(io::ErrorKind::PermissionDenied)), IpcError::Other(io::ErrorKind::PermissionDenied));
+
+        // OS errors keep their code unless they have a variant
+        let code = io::Error::from(io::ErrorKind::NotFound).raw_os_error().unwrap_or(2);
+        assert_eq!(IpcError::from(io::Error::from_raw_os_error(code)), IpcError::Os(code));
+        assert_eq!(io::Error::from(IpcError::Os(code)).raw_os_error(), Some(code));
+    }
+}
@@ -1,276 +1,143 @@
-#![allow(dead_code)]
-use windows_sys::Win32::Foundation::*;
-use windows_sys::Win32::Storage::FileSystem::*;
-use windows_sys::Win32::System::Pipes::*;
-use windows_sys::Win32::System::IO::*;
-use windows_sys::Win32::System::Threading::*;
-
-fn get_last_error() -> u32 {
-    unsafe {
-        GetLastError()
-    }
+pub mod client;
+pub mod control;
+mod error;
+pub use error::IpcError;
+pub mod loopback;
+pub mod protocol;
+use protocol::Backend;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+#[cfg(windows)]
+mod named_pipe;
+#[cfg(windows)]
+mod overlapped;
+#[cfg(windows)]
+pub use overlapped::Overlapped;
+#[cfg(windows)]
+pub use named_pipe::NamedPipe;
+#[cfg(windows)]
+pub use named_pipe::NamedPipeListener;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+pub use unix::UnixSocket;
+#[cfg(unix)]
+pub use unix::UnixSocketListener;
+
+/// Transport used by the capture library and proxy on the current platform.
+#[cfg(windows)]
+pub type Pipe = NamedPipe;
+#[cfg(windows)]
+pub type PipeListener = NamedPipeListener;
+#[cfg(unix)]
+pub type Pipe = UnixSocket;
+#[cfg(unix)]
+pub type PipeListener = UnixSocketListener;
+
+/// Start of every capture library address. The rest is the id of the
+/// process the library is loaded in and the name of its [`Backend`].
+#[cfg(windows)]
+pub const ADDRESS_PREFIX: &str = "\\\\.\\pipe\\net.wartide.d4.tts-air-";
+#[cfg(unix)]
+pub const ADDRESS_PREFIX: &str = "/tmp/net.wartide.d4.tts-air-";
+
+/// Address shared by every capture library before addresses included the
+/// process id. Found by [`discover`] as instance 0.
+#[cfg(windows)]
+pub const WARTIDE_ADDRESS: &str = "\\\\.\\pipe\\net.wartide.d4.tts-air-0\0";
+#[cfg(unix)]
+pub const WARTIDE_ADDRESS: &str = "/tmp/net.wartide.d4.tts-air-0\0";
+
+/// NUL-terminated address of the `backend` capture library in process `pid`.
+///
+/// Every backend has its own address so a game can load more than one.
+pub fn instance_address(pid: u32, backend: Backend) -> String {
+    format!("{ADDRESS_PREFIX}{pid}-{}\0", backend.name())
 }
 
-pub const WARTIDE_ADDRESS: &'static str = "\\\\.\\pipe\\net.wartide.d4.tts-air-0\0";
-
-pub struct NamedPipeListener {
-    inner: Option<(HANDLE, Event)>,
+/// Process id and backend of the capture library listening on `address`.
+///
+/// Addresses of libraries from before every backend had its own have an
+/// [`Backend::Unknown`] backend.
+pub fn instance_id(address: &str) -> Option<(u32, Backend)> {
+    let instance = address.trim_end_matches('\0').strip_prefix(ADDRESS_PREFIX)?;
+    let (pid, backend) = match instance.split_once('-') {
+        Some((pid, name)) => (pid, Backend::from_name(name)?),
+        None => (instance, Backend::Unknown),
+    };
+    Some((pid.parse().ok()?, backend))
 }
 
-impl NamedPipeListener {
-    pub fn bind(path: &str) -> Result<Self, u32> {
-        if path.len() >= 1000 {
-            return Err(u32::MAX);
-        }
-
-        let mut buffer = [0; 1024];
-        let mut len = 0;
-        for c in path.encode_utf16() {
-            buffer[len] = c;
-            len += 1;
-        }
-
-        let hwnd = unsafe {
-            CreateNamedPipeW(
-                buffer.as_ptr(),
-                //PIPE_ACCESS_OUTBOUND,
-                PIPE_ACCESS_DUPLEX
-                | FILE_FLAG_OVERLAPPED,
-                PIPE_TYPE_BYTE
-                | PIPE_READMODE_BYTE
-                | PIPE_WAIT
-                | PIPE_REJECT_REMOTE_CLIENTS,
-                PIPE_UNLIMITED_INSTANCES,
-                2048,
-                0,
-                0,
-                core::ptr::null_mut(),
-            )
-        };
-
-        if hwnd == INVALID_HANDLE_VALUE {
-            return Err(get_last_error());
-        }
-
-        let event = unsafe {
-            CreateEventW(
-                core::ptr::null_mut(),
-                0,
-                0,
-                core::ptr::null_mut(),
-            )
-        };
-
-        if event == 0 {
-            return Err(get_last_error());
-        }
-
-        let event = Event(event);
-
-        Ok(Self {
-            inner: Some((hwnd, event)),
+/// Capture libraries listening on `T` as their process id, backend and
+/// NUL-terminated address, ordered by process id.
+pub fn discover<T: Transport>() -> Vec<(u32, Backend, String)> {
+    let mut instances = T::discover(ADDRESS_PREFIX)
+        .into_iter()
+        .filter_map(|address| {
+            let (pid, backend) = instance_id(&address)?;
+            Some((pid, backend, address))
         })
-    }
-
-    pub fn listen(&mut self) -> Result<NamedPipe, u32> {
-        self.listen_timeout_ms_(None)
-    }
-
-    pub fn listen_timeout_ms(&mut self, ms: u32) -> Result<NamedPipe, u32> {
-        self.listen_timeout_ms_(Some(ms))
-    }
-
-    fn listen_timeout_ms_(&mut self, ms: Option<u32>) -> Result<NamedPipe, u32> {
-        if let Some((hwnd, event)) = self.inner.as_mut() {
-            // TODO: lifetime with event in OVERLAPPED
-            unsafe {
-                let mut ow: OVERLAPPED = core::mem::zeroed();
-                ow.hEvent = event.0;
-                let err = ConnectNamedPipe(*hwnd, &mut ow);
-                if err == 0 {
-                    match get_last_error() {
-                        ERROR_PIPE_CONNECTED => (),
-                        ERROR_IO_PENDING => {
-                            if let Some(ms) = ms {
-                                WaitForSingleObject(event.0, ms);
-                            } else {
-                                WaitForSingleObject(event.0, INFINITE);
-                            }
-
-                            let mut ow2: OVERLAPPED = core::mem::zeroed();
-                            ow2.hEvent = event.0;
-                            let err = ConnectNamedPipe(*hwnd, &mut ow2);
-                            if err == 0 {
-                                match get_last_error() {
-                                    ERROR_PIPE_CONNECTED => (),
-                                    e => return Err(e),
-                                }
-                            }
-                        }
-                        e => return Err(e),
-                    }
-                }
-            }
-        }
-
-        let Some((hwnd, event)) = self.inner.take() else {
-            log::warn!("NamedPipeListener::inner is None");
-            return Err(0x00000006/*ERROR_INVALID_HANDLE*/);
-        };
-
-        Ok(NamedPipe {
-            hwnd,
-            is_server: Some(event),
-        })
-    }
-}
-
-impl Drop for NamedPipeListener {
-    fn drop(&mut self) {
-        if let Some((hwnd, _)) = self.inner.take() {
-            unsafe {
-                FlushFileBuffers(hwnd);
-                DisconnectNamedPipe(hwnd);
-                CloseHandle(hwnd);
-            }
-        }
-    }
-}
-
-pub struct NamedPipe {
-    hwnd: HANDLE,
-    is_server: Option<Event>,
+        .collect::<Vec<_>>();
+    instances.sort();
+    instances.dedup();
+    instances
 }
 
-unsafe impl Send for NamedPipe {}
+/// Server side of a [`Transport`].
+///
+/// A bound listener yields a single connection. After a successful
+/// [`Listener::listen`] it needs to be bound again to accept another client.
+pub trait Listener: Sized {
+    type Transport: Transport;
 
-impl NamedPipe {
-    pub fn open(path: &str) -> Result<Self, u32> {
-        if path.is_empty() || path.as_bytes()[path.len() - 1] != 0 {
-            return Err(u32::MAX);
-        }
+    /// Bind to a NUL-terminated address like [`WARTIDE_ADDRESS`].
+    fn bind(path: &str) -> Result<Self, IpcError>;
 
-        let hwnd = unsafe {
-            CreateFileA(
-                path.as_ptr(),
-                GENERIC_READ | GENERIC_WRITE,
-                FILE_SHARE_READ | FILE_SHARE_WRITE,
-                core::ptr::null_mut(),
-                OPEN_EXISTING,
-                0,
-                0,
-            )
-        };
+    fn listen(&mut self) -> Result<Self::Transport, IpcError>;
 
-        if hwnd != INVALID_HANDLE_VALUE {
-            Ok(NamedPipe {
-                hwnd,
-                is_server: None,
-            })
-        } else {
-            Err(get_last_error())
-        }
-    }
+    /// Fails with [`IpcError::Timeout`] if no client connected in time.
+    fn listen_timeout_ms(&mut self, ms: u32) -> Result<Self::Transport, IpcError>;
+}
 
-    pub fn recv(&mut self, buffer: &mut [u8]) -> Result<u64, u32> {
-        debug_assert!(self.is_server.is_none());
-        if self.hwnd == INVALID_HANDLE_VALUE {
-            log::warn!("NamedPipe::hwnd is NULL");
-            return Err(0x00000006/*ERROR_INVALID_HANDLE*/);
-        }
+/// Connected byte stream between the capture library and a client.
+pub trait Transport: Send + Sized {
+    /// Connect to a NUL-terminated address like [`WARTIDE_ADDRESS`].
+    fn open(path: &str) -> Result<Self, IpcError>;
 
-        let mut read = 0;
-        let err = unsafe {
-            ReadFile(
-                self.hwnd,
-                buffer.as_mut_ptr() as *mut _,
-                u32::try_from(buffer.len()).unwrap(),
-                &mut read,
-                core::ptr::null_mut(),
-            )
-        };
+    /// Fails with [`IpcError::Disconnected`] once the peer is gone.
+    fn recv(&mut self, buffer: &mut [u8]) -> Result<u64, IpcError>;
 
-        if err != 0 {
-            Ok(read as u64)
-        } else {
-            Err(get_last_error())
-        }
-    }
+    fn send(&mut self, msg: &[u8]) -> Result<u64, IpcError>;
 
-    pub fn send(&mut self, msg: &[u8]) -> Result<u64, u32> {
-        if self.hwnd == INVALID_HANDLE_VALUE {
-            log::warn!("NamedPipe::hwnd is NULL");
-            return Err(0x00000006/*ERROR_INVALID_HANDLE*/);
-        }
+    /// Another handle to the same connection so one thread can read while
+    /// another one writes. The connection stays open until every handle is
+    /// dropped or [`Transport::shutdown`] is called.
+    fn try_clone(&self) -> Result<Self, IpcError>;
 
-        if msg.len() > u32::MAX as usize {
-            log::error!("message exceeds u32::MAX ({})", msg.len());
-        }
+    /// Close the connection for every handle, failing their pending and
+    /// later [`Transport::send`] and [`Transport::recv`] calls. The peer
+    /// sees the connection close.
+    fn shutdown(&self) -> Result<(), IpcError>;
 
-        let mut wrote = 0;
-        if unsafe {
-            // server NamedPipe was opened with OVERLAPPED
-            if let Some(event) = &mut self.is_server {
-                let mut ow: OVERLAPPED = core::mem::zeroed();
-                ow.hEvent = event.0;
-                let err = WriteFile(
-                    self.hwnd,
-                    msg.as_ptr(),
-                    msg.len().min(u32::MAX as usize) as u32,
-                    &mut wrote,
-                    &mut ow,
-                );
-                if err == 0 {
-                    match get_last_error() {
-                        ERROR_IO_PENDING => WaitForSingleObject(event.0, INFINITE),
-                        _ => return Err(get_last_error()),
-                    };
-                }
-                1
-            } else {
-                WriteFile(
-                    self.hwnd,
-                    msg.as_ptr(),
-                    msg.len().min(u32::MAX as usize) as u32,
-                    &mut wrote,
-                    core::ptr::null_mut(),
-                )
-            }
-        } == 0 {
-            Err(get_last_error())
-        } else {
-            // TODO: verify size is correct when async (is_server.is_some())
-            Ok(msg.len() as u64)
-        }
-    }
+    /// NUL-terminated addresses starting with `prefix` that a listener may
+    /// be bound to. Addresses left behind by an exited process can be
+    /// included.
+    fn discover(prefix: &str) -> Vec<String>;
 }
 
-impl Drop for NamedPipe {
-    fn drop(&mut self) {
-        if self.hwnd != INVALID_HANDLE_VALUE {
-            unsafe {
-                if self.is_server.is_some() {
-                    FlushFileBuffers(self.hwnd);
-                    DisconnectNamedPipe(self.hwnd);
-                }
-                CloseHandle(self.hwnd);
-            }
-            self.hwnd = INVALID_HANDLE_VALUE;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_addresses() {
+        let address = instance_address(1234, Backend::Nvda);
+        assert_eq!(instance_id(&address), Some((1234, Backend::Nvda)));
+        assert_eq!(instance_id(&instance_address(1234, Backend::Saapi)), Some((1234, Backend::Saapi)));
+        assert_eq!(instance_id(WARTIDE_ADDRESS), Some((0, Backend::Unknown)));
+        assert_eq!(instance_id(&format!("{ADDRESS_PREFIX}1234-other\0")), None);
+        assert_eq!(instance_id(&format!("{ADDRESS_PREFIX}bench\0")), None);
     }
 }
-
-#[repr(transparent)]
-#[derive(Clone)]
-struct Event(HANDLE);
-
-impl Drop for Event {
-    fn drop(&mut self) {
-        if self.0 != 0 {
-            unsafe {
-                CloseHandle(self.0);
-            }
-            self.0 = 0;
-        }
-    }
-}
\ No newline at end of file
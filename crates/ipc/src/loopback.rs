@@ -0,0 +1,312 @@
+//! In-process [`Listener`] and [`Transport`] for tests.
+//!
+//! Addresses live in a registry shared by the whole process and never touch
+//! the file system, so tests can use [`WARTIDE_ADDRESS`](crate::WARTIDE_ADDRESS)
+//! even with the real capture library running. Like a named pipe a bound
+//! listener accepts a single connection and [`Transport::open`] fails while
+//! no listener is waiting.
+use std::io;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use crate::IpcError;
+use crate::Listener;
+use crate::Transport;
+
+/// Write half of a connection, shared with the registry so
+/// [`disconnect`] can close it.
+type Link = Mutex<Option<Sender<Vec<u8>>>>;
+
+struct Waiting {
+    address: String,
+    id: u64,
+    accept: Sender<LoopbackPipe>,
+}
+
+static WAITING: Mutex<Vec<Waiting>> = Mutex::new(Vec::new());
+static LINKS: Mutex<Vec<(String, Weak<Link>)>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn check_address(path: &str) -> Result<&str, IpcError> {
+    match path.strip_suffix('\0') {
+        Some(path) if !path.is_empty() => Ok(path),
+        _ => Err(IpcError::InvalidPath),
+    }
+}
+
+/// Close every connection made to `path` as if the peer went away.
+///
+/// Data already sent can still be read.
+pub fn disconnect(path: &str) {
+    let path = path.trim_end_matches('\0');
+    let mut closing = Vec::new();
+    LINKS.lock().unwrap().retain(|(address, link)| {
+        let Some(link) = link.upgrade() else {
+            return false;
+        };
+        if address != path {
+            return true;
+        }
+
+        closing.push(link);
+        false
+    });
+
+    // hold every end before closing any so neither side can still send
+    // after seeing the other one close
+    let mut guards = closing.iter().map(|link| link.lock().unwrap()).collect::<Vec<_>>();
+    for guard in &mut guards {
+        guard.take();
+    }
+}
+
+pub struct LoopbackListener {
+    id: u64,
+    accept: Option<Receiver<LoopbackPipe>>,
+}
+
+impl Listener for LoopbackListener {
+    type Transport = LoopbackPipe;
+
+    fn bind(path: &str) -> Result<Self, IpcError> {
+        let address = check_address(path)?.to_string();
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let (send, recv) = mpsc::channel();
+        WAITING.lock().unwrap().push(Waiting {
+            address,
+            id,
+            accept: send,
+        });
+
+        Ok(Self {
+            id,
+            accept: Some(recv),
+        })
+    }
+
+    fn listen(&mut self) -> Result<LoopbackPipe, IpcError> {
+        let Some(accept) = self.accept.take() else {
+            return Err(IpcError::InvalidHandle);
+        };
+
+        accept.recv().map_err(|_| IpcError::InvalidHandle)
+    }
+
+    fn listen_timeout_ms(&mut self, ms: u32) -> Result<LoopbackPipe, IpcError> {
+        let Some(accept) = &self.accept else {
+            return Err(IpcError::InvalidHandle);
+        };
+
+        match accept.recv_timeout(Duration::from_millis(ms as u64)) {
+            Ok(pipe) => {
+                self.accept = None;
+                Ok(pipe)
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => Err(IpcError::Timeout),
+            Err(mpsc::RecvTimeoutError::Disconnected) => Err(IpcError::InvalidHandle),
+        }
+    }
+}
+
+impl Drop for LoopbackListener {
+    fn drop(&mut self) {
+        if let Ok(mut waiting) = WAITING.lock() {
+            waiting.retain(|w| w.id != self.id);
+        }
+    }
+}
+
+/// One end of an in-process connection.
+pub struct LoopbackPipe {
+    end: Arc<End>,
+    /// Rest of a message that did not fit the last `recv` buffer.
+    pending: Vec<u8>,
+}
+
+/// State shared by every handle of one end, which closes the connection once
+/// the last handle is dropped.
+struct End {
+    send: Arc<Link>,
+    /// Write half of the other end, closed along with `send`.
+    peer: Arc<Link>,
+    recv: Mutex<Receiver<Vec<u8>>>,
+}
+
+impl End {
+    fn close(&self) {
+        for link in [&self.send, &self.peer] {
+            if let Ok(mut link) = link.lock() {
+                link.take();
+            }
+        }
+    }
+}
+
+impl Drop for End {
+    fn drop(&mut self) {
+        // like a socket, closing the last handle closes both directions
+        self.close();
+    }
+}
+
+impl LoopbackPipe {
+    fn pair(address: &str) -> (Self, Self) {
+        let (send_a, recv_b) = mpsc::channel();
+        let (send_b, recv_a) = mpsc::channel();
+        let send_a = Arc::new(Mutex::new(Some(send_a)));
+        let send_b = Arc::new(Mutex::new(Some(send_b)));
+
+        let mut links = LINKS.lock().unwrap();
+        links.retain(|(_, link)| link.strong_count() > 0);
+        links.push((address.to_string(), Arc::downgrade(&send_a)));
+        links.push((address.to_string(), Arc::downgrade(&send_b)));
+
+        let a = Self::new(send_a.clone(), send_b.clone(), recv_a);
+        let b = Self::new(send_b, send_a, recv_b);
+        (a, b)
+    }
+
+    fn new(send: Arc<Link>, peer: Arc<Link>, recv: Receiver<Vec<u8>>) -> Self {
+        Self {
+            end: Arc::new(End {
+                send,
+                peer,
+                recv: Mutex::new(recv),
+            }),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Transport for LoopbackPipe {
+    fn open(path: &str) -> Result<Self, IpcError> {
+        let address = check_address(path)?;
+
+        // a listener is spent by its first connection
+        let waiting = {
+            let mut waiting = WAITING.lock().unwrap();
+            let i = waiting.iter().position(|w| w.address == address);
+            i.map(|i| waiting.remove(i))
+        };
+        let Some(waiting) = waiting else {
+            return Err(IpcError::Other(io::ErrorKind::NotFound));
+        };
+
+        let (client, server) = Self::pair(address);
+        waiting.accept
+            .send(server)
+            .map_err(|_| IpcError::Other(io::ErrorKind::NotFound))?;
+        Ok(client)
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> Result<u64, IpcError> {
+        if self.pending.is_empty() {
+            let recv = self.end.recv.lock().unwrap();
+            self.pending = recv.recv().map_err(|_| IpcError::Disconnected)?;
+        }
+
+        let len = self.pending.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        Ok(len as u64)
+    }
+
+    fn send(&mut self, msg: &[u8]) -> Result<u64, IpcError> {
+        let send = self.end.send.lock().unwrap();
+        let Some(send) = &*send else {
+            return Err(IpcError::Disconnected);
+        };
+
+        send.send(msg.to_vec()).map_err(|_| IpcError::Disconnected)?;
+        Ok(msg.len() as u64)
+    }
+
+    fn try_clone(&self) -> Result<Self, IpcError> {
+        Ok(Self {
+            end: self.end.clone(),
+            pending: Vec::new(),
+        })
+    }
+
+    fn shutdown(&self) -> Result<(), IpcError> {
+        self.end.close();
+        Ok(())
+    }
+
+    fn discover(prefix: &str) -> Vec<String> {
+        WAITING.lock().unwrap()
+            .iter()
+            .filter(|w| w.address.starts_with(prefix))
+            .map(|w| format!("{}\0", w.address))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn connect_and_disconnect() {
+        const ADDRESS: &str = "loopback-test\0";
+        assert_eq!(LoopbackPipe::open(ADDRESS).err(), Some(IpcError::Other(io::ErrorKind::NotFound)));
+
+        let mut listener = LoopbackListener::bind(ADDRESS).unwrap();
+        let server = thread::spawn(move || {
+            let mut pipe = listener.listen().unwrap();
+            pipe.send(b"hello").unwrap();
+            let mut buffer = [0; 8];
+            assert_eq!(pipe.recv(&mut buffer), Err(IpcError::Disconnected));
+            assert_eq!(pipe.send(b"gone"), Err(IpcError::Disconnected));
+        });
+
+        let mut client = loop {
+            match LoopbackPipe::open(ADDRESS) {
+                Ok(client) => break client,
+                Err(_) => thread::yield_now(),
+            }
+        };
+        // the listener was spent by the first connection
+        assert!(LoopbackPipe::open(ADDRESS).is_err());
+
+        // reads are split across small buffers
+        let mut buffer = [0; 3];
+        assert_eq!(client.recv(&mut buffer), Ok(3));
+        assert_eq!(&buffer, b"hel");
+        assert_eq!(client.recv(&mut buffer), Ok(2));
+        assert_eq!(&buffer[..2], b"lo");
+
+        disconnect(ADDRESS);
+        server.join().unwrap();
+        assert_eq!(client.recv(&mut buffer), Err(IpcError::Disconnected));
+    }
+
+    #[test]
+    fn last_handle_closes() {
+        const ADDRESS: &str = "loopback-clone-test\0";
+        let mut listener = LoopbackListener::bind(ADDRESS).unwrap();
+        let mut client = LoopbackPipe::open(ADDRESS).unwrap();
+        let server = listener.listen().unwrap();
+
+        // dropping one handle keeps the connection open for the others
+        let mut clone = server.try_clone().unwrap();
+        drop(server);
+        client.send(b"ping").unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(clone.recv(&mut buffer), Ok(4));
+
+        let mut other = clone.try_clone().unwrap();
+        clone.shutdown().unwrap();
+        assert_eq!(other.send(b"ping"), Err(IpcError::Disconnected));
+        assert_eq!(client.recv(&mut buffer), Err(IpcError::Disconnected));
+    }
+}
@@ -0,0 +1,445 @@
+#![allow(dead_code)]
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use windows_sys::Win32::Foundation::*;
+use windows_sys::Win32::Storage::FileSystem::*;
+use windows_sys::Win32::System::Pipes::*;
+use windows_sys::Win32::System::Threading::*;
+use windows_sys::Win32::System::IO::CancelIoEx;
+
+use crate::IpcError;
+use crate::Listener;
+use crate::Transport;
+use crate::overlapped::Overlapped;
+
+fn get_last_error() -> u32 {
+    unsafe {
+        GetLastError()
+    }
+}
+
+fn last_error() -> IpcError {
+    IpcError::from_os(get_last_error() as i32)
+}
+
+pub struct NamedPipeListener {
+    inner: Option<(HANDLE, Event)>,
+    /// Connect started by an earlier listen that timed out.
+    connecting: Option<Overlapped<'static>>,
+}
+
+impl Listener for NamedPipeListener {
+    type Transport = NamedPipe;
+
+    fn bind(path: &str) -> Result<Self, IpcError> {
+        if path.is_empty() || path.len() >= 1000 || !path.ends_with('\0') {
+            return Err(IpcError::InvalidPath);
+        }
+
+        let mut buffer = [0; 1024];
+        let mut len = 0;
+        for c in path.encode_utf16() {
+            buffer[len] = c;
+            len += 1;
+        }
+
+        let hwnd = unsafe {
+            CreateNamedPipeW(
+                buffer.as_ptr(),
+                //PIPE_ACCESS_OUTBOUND,
+                PIPE_ACCESS_DUPLEX
+                | FILE_FLAG_OVERLAPPED,
+                PIPE_TYPE_BYTE
+                | PIPE_READMODE_BYTE
+                | PIPE_WAIT
+                | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                2048,
+                0,
+                0,
+                core::ptr::null_mut(),
+            )
+        };
+
+        if hwnd == INVALID_HANDLE_VALUE {
+            return Err(last_error());
+        }
+
+        let event = match Event::new() {
+            Ok(event) => event,
+            Err(e) => {
+                unsafe {
+                    CloseHandle(hwnd);
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(Self {
+            inner: Some((hwnd, event)),
+            connecting: None,
+        })
+    }
+
+    fn listen(&mut self) -> Result<NamedPipe, IpcError> {
+        self.listen_timeout_ms_(None)
+    }
+
+    fn listen_timeout_ms(&mut self, ms: u32) -> Result<NamedPipe, IpcError> {
+        self.listen_timeout_ms_(Some(ms))
+    }
+}
+
+impl NamedPipeListener {
+    fn listen_timeout_ms_(&mut self, ms: Option<u32>) -> Result<NamedPipe, IpcError> {
+        let Some((hwnd, event)) = &self.inner else {
+            log::warn!("NamedPipeListener::inner is None");
+            return Err(IpcError::InvalidHandle);
+        };
+        let (hwnd, event) = (*hwnd, event.0);
+
+        let connecting = match &mut self.connecting {
+            Some(connecting) => connecting,
+            // `hwnd` and `event` outlive the operation since it is dropped
+            // before them
+            None => self.connecting.insert(unsafe { Overlapped::connect(hwnd, event)? }),
+        };
+
+        match connecting.wait(ms) {
+            Ok(_) => (),
+            // keep waiting on the same connect next time
+            Err(IpcError::Timeout) => return Err(IpcError::Timeout),
+            Err(e) => {
+                self.connecting = None;
+                return Err(e);
+            }
+        }
+        self.connecting = None;
+
+        let Some((hwnd, event)) = self.inner.take() else {
+            return Err(IpcError::InvalidHandle);
+        };
+
+        Ok(NamedPipe {
+            handle: Handle::new(hwnd, true),
+            event,
+        })
+    }
+}
+
+impl Drop for NamedPipeListener {
+    fn drop(&mut self) {
+        // cancel before the handle is closed
+        self.connecting = None;
+        if let Some((hwnd, _)) = self.inner.take() {
+            unsafe {
+                FlushFileBuffers(hwnd);
+                DisconnectNamedPipe(hwnd);
+                CloseHandle(hwnd);
+            }
+        }
+    }
+}
+
+/// Both ends are opened with `FILE_FLAG_OVERLAPPED` so clones can read and
+/// write at the same time.
+pub struct NamedPipe {
+    handle: Arc<Handle>,
+    /// Every clone needs its own event to wait on its own operations.
+    event: Event,
+}
+
+unsafe impl Send for NamedPipe {}
+
+/// Pipe handle shared by every clone of a connection.
+///
+/// It is closed once the last clone is dropped, or after
+/// [`Transport::shutdown`] once no operation uses it anymore, so the peer
+/// sees the connection close either way.
+struct Handle {
+    hwnd: HANDLE,
+    is_server: bool,
+    state: Mutex<HandleState>,
+}
+
+struct HandleState {
+    /// Set by [`Transport::shutdown`] to fail every later operation.
+    closed: bool,
+    /// Operations started with the handle that didn't finish yet.
+    running: usize,
+    /// Whether `hwnd` wasn't closed yet.
+    open: bool,
+}
+
+impl Handle {
+    fn new(hwnd: HANDLE, is_server: bool) -> Arc<Self> {
+        Arc::new(Self {
+            hwnd,
+            is_server,
+            state: Mutex::new(HandleState {
+                closed: false,
+                running: 0,
+                open: true,
+            }),
+        })
+    }
+
+    /// Start an operation with `start` and wait for it unless the connection
+    /// was shut down. Returns the number of bytes transferred.
+    fn run<'a>(
+        &self,
+        start: impl FnOnce(HANDLE) -> Result<Overlapped<'a>, IpcError>,
+    ) -> Result<u32, IpcError> {
+        // started while locked so a shutdown can't miss it when cancelling
+        let mut op = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return Err(IpcError::Disconnected);
+            }
+            let op = start(self.hwnd)?;
+            state.running += 1;
+            op
+        };
+
+        let result = op.wait(None);
+        drop(op);
+
+        let mut state = self.state.lock().unwrap();
+        state.running -= 1;
+        if state.closed {
+            self.close(&mut state);
+            // cancelled by the shutdown
+            return result.map_err(|_| IpcError::Disconnected);
+        }
+        result
+    }
+
+    fn shutdown(&self) -> Result<(), IpcError> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Ok(());
+        }
+        state.closed = true;
+
+        let mut result = Ok(());
+        unsafe {
+            // forces the client end closed right away
+            if self.is_server && DisconnectNamedPipe(self.hwnd) == 0 && get_last_error() != ERROR_PIPE_NOT_CONNECTED {
+                result = Err(last_error());
+            }
+            // wakes up operations of every clone, which close the handle
+            // once the last one is done
+            if CancelIoEx(self.hwnd, core::ptr::null()) == 0 && get_last_error() != ERROR_NOT_FOUND {
+                result = result.and(Err(last_error()));
+            }
+        }
+        self.close(&mut state);
+        result
+    }
+
+    /// Close the handle unless an operation still uses it.
+    fn close(&self, state: &mut HandleState) {
+        if state.open && state.running == 0 {
+            unsafe {
+                CloseHandle(self.hwnd);
+            }
+            state.open = false;
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap_or_else(|e| e.into_inner());
+        if state.open {
+            unsafe {
+                if self.is_server {
+                    FlushFileBuffers(self.hwnd);
+                    DisconnectNamedPipe(self.hwnd);
+                }
+                CloseHandle(self.hwnd);
+            }
+            state.open = false;
+        }
+    }
+}
+
+impl Transport for NamedPipe {
+    fn open(path: &str) -> Result<Self, IpcError> {
+        if path.is_empty() || !path.ends_with('\0') {
+            return Err(IpcError::InvalidPath);
+        }
+
+        let hwnd = unsafe {
+            CreateFileA(
+                path.as_ptr(),
+                GENERIC_READ | GENERIC_WRITE,
+                FILE_SHARE_READ | FILE_SHARE_WRITE,
+                core::ptr::null_mut(),
+                OPEN_EXISTING,
+                FILE_FLAG_OVERLAPPED,
+                0,
+            )
+        };
+
+        if hwnd == INVALID_HANDLE_VALUE {
+            return Err(last_error());
+        }
+
+        match Event::new() {
+            Ok(event) => Ok(NamedPipe {
+                handle: Handle::new(hwnd, false),
+                event,
+            }),
+            Err(e) => {
+                unsafe {
+                    CloseHandle(hwnd);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> Result<u64, IpcError> {
+        let event = self.event.0;
+        // the handle and event outlive the operation since it is waited on
+        // before returning
+        self.handle.run(|hwnd| unsafe { Overlapped::read(hwnd, event, buffer) })
+            .map(|read| read as u64)
+    }
+
+    fn send(&mut self, msg: &[u8]) -> Result<u64, IpcError> {
+        if msg.len() > u32::MAX as usize {
+            log::error!("message exceeds u32::MAX ({})", msg.len());
+        }
+
+        let event = self.event.0;
+        self.handle.run(|hwnd| unsafe { Overlapped::write(hwnd, event, msg) })
+            .map(|wrote| wrote as u64)
+    }
+
+    fn try_clone(&self) -> Result<Self, IpcError> {
+        Ok(NamedPipe {
+            handle: self.handle.clone(),
+            event: Event::new()?,
+        })
+    }
+
+    fn shutdown(&self) -> Result<(), IpcError> {
+        self.handle.shutdown()
+    }
+
+    fn discover(prefix: &str) -> Vec<String> {
+        const PIPES: &str = "\\\\.\\pipe\\";
+
+        let pattern = format!("{PIPES}*\0").encode_utf16().collect::<Vec<u16>>();
+        let mut data = unsafe { core::mem::zeroed::<WIN32_FIND_DATAW>() };
+        let find = unsafe { FindFirstFileW(pattern.as_ptr(), &mut data) };
+        if find == INVALID_HANDLE_VALUE {
+            return Vec::new();
+        }
+
+        let mut addresses = Vec::new();
+        loop {
+            let name = &data.cFileName;
+            let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+            let address = format!("{PIPES}{}", String::from_utf16_lossy(&name[..len]));
+            if address.starts_with(prefix) {
+                addresses.push(address + "\0");
+            }
+
+            if unsafe { FindNextFileW(find, &mut data) } == 0 {
+                break;
+            }
+        }
+
+        unsafe {
+            FindClose(find);
+        }
+        addresses
+    }
+}
+
+#[repr(transparent)]
+struct Event(HANDLE);
+
+impl Event {
+    /// Manual-reset event as required by [`Overlapped`].
+    fn new() -> Result<Self, IpcError> {
+        let event = unsafe {
+            CreateEventW(
+                core::ptr::null_mut(),
+                1,
+                0,
+                core::ptr::null_mut(),
+            )
+        };
+
+        if event == 0 {
+            Err(last_error())
+        } else {
+            Ok(Event(event))
+        }
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        if self.0 != 0 {
+            unsafe {
+                CloseHandle(self.0);
+            }
+            self.0 = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(name: &str) -> (NamedPipe, NamedPipe) {
+        let address = format!("\\\\.\\pipe\\tts-air-{name}-test-{}\0", std::process::id());
+        let mut listener = NamedPipeListener::bind(&address).unwrap();
+        let client = NamedPipe::open(&address).unwrap();
+        let server = listener.listen().unwrap();
+        (server, client)
+    }
+
+    #[test]
+    fn last_handle_closes() {
+        let (server, mut client) = connect("clone");
+
+        // dropping one handle keeps the connection open for the others
+        let mut clone = server.try_clone().unwrap();
+        drop(server);
+        client.send(b"ping").unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(clone.recv(&mut buffer), Ok(4));
+
+        drop(clone);
+        assert_eq!(client.recv(&mut buffer), Err(IpcError::Disconnected));
+    }
+
+    #[test]
+    fn client_shutdown() {
+        let (mut server, client) = connect("client-shutdown");
+        let mut reader = client.try_clone().unwrap();
+        let mut writer = client.try_clone().unwrap();
+
+        // a read in another thread fails whether or not it started yet
+        let blocked = std::thread::spawn(move || {
+            let mut buffer = [0; 4];
+            reader.recv(&mut buffer)
+        });
+        client.shutdown().unwrap();
+        assert_eq!(blocked.join().unwrap(), Err(IpcError::Disconnected));
+
+        // later calls fail and the server sees the connection close
+        assert_eq!(writer.send(b"ping"), Err(IpcError::Disconnected));
+        let mut buffer = [0; 4];
+        assert_eq!(writer.recv(&mut buffer), Err(IpcError::Disconnected));
+        assert_eq!(server.recv(&mut buffer), Err(IpcError::Disconnected));
+    }
+}
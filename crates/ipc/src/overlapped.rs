@@ -0,0 +1,151 @@
+use core::marker::PhantomData;
+use core::pin::Pin;
+
+use windows_sys::Win32::Foundation::*;
+use windows_sys::Win32::Storage::FileSystem::*;
+use windows_sys::Win32::System::Pipes::*;
+use windows_sys::Win32::System::IO::*;
+use windows_sys::Win32::System::Threading::*;
+
+use crate::IpcError;
+
+/// Asynchronous I/O started on a handle opened with `FILE_FLAG_OVERLAPPED`.
+///
+/// The `OVERLAPPED` lives in a pinned box so it keeps its address while the
+/// kernel owns it, and `'a` keeps the buffer borrowed for as long. Dropping an
+/// unfinished operation cancels it with `CancelIoEx` and waits until the
+/// kernel is done with both.
+pub struct Overlapped<'a> {
+    handle: HANDLE,
+    inner: Pin<Box<OVERLAPPED>>,
+    state: State,
+    _buffer: PhantomData<&'a mut [u8]>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    /// Completed with the number of bytes transferred.
+    Done(u32),
+}
+
+impl Overlapped<'static> {
+    /// Wait for a client to connect to the named pipe `handle`.
+    ///
+    /// # Safety
+    /// `handle` and `event` need to outlive the operation. `event` needs to be
+    /// a manual-reset event.
+    pub unsafe fn connect(handle: HANDLE, event: HANDLE) -> Result<Self, IpcError> {
+        Self::start(handle, event, |ow| ConnectNamedPipe(handle, ow))
+    }
+}
+
+impl<'a> Overlapped<'a> {
+    /// Read from `handle` into `buffer`.
+    ///
+    /// # Safety
+    /// See [`Overlapped::connect`].
+    pub unsafe fn read(handle: HANDLE, event: HANDLE, buffer: &'a mut [u8]) -> Result<Self, IpcError> {
+        let len = buffer.len().min(u32::MAX as usize) as u32;
+        let ptr = buffer.as_mut_ptr();
+        Self::start(handle, event, |ow| ReadFile(handle, ptr as *mut _, len, core::ptr::null_mut(), ow))
+    }
+
+    /// Write `buffer` to `handle`.
+    ///
+    /// # Safety
+    /// See [`Overlapped::connect`].
+    pub unsafe fn write(handle: HANDLE, event: HANDLE, buffer: &'a [u8]) -> Result<Self, IpcError> {
+        let len = buffer.len().min(u32::MAX as usize) as u32;
+        let ptr = buffer.as_ptr();
+        Self::start(handle, event, |ow| WriteFile(handle, ptr, len, core::ptr::null_mut(), ow))
+    }
+
+    unsafe fn start(
+        handle: HANDLE,
+        event: HANDLE,
+        f: impl FnOnce(*mut OVERLAPPED) -> BOOL,
+    ) -> Result<Self, IpcError> {
+        let mut ow: OVERLAPPED = core::mem::zeroed();
+        ow.hEvent = event;
+        let mut op = Self {
+            handle,
+            inner: Box::pin(ow),
+            state: State::Pending,
+            _buffer: PhantomData,
+        };
+
+        if f(op.as_mut_ptr()) == 0 {
+            match GetLastError() {
+                ERROR_IO_PENDING => return Ok(op),
+                // client connected between CreateNamedPipe and ConnectNamedPipe
+                ERROR_PIPE_CONNECTED => op.state = State::Done(0),
+                e => {
+                    // nothing was queued so there is nothing to cancel
+                    op.state = State::Done(0);
+                    return Err(IpcError::from_os(e as i32));
+                }
+            }
+        } else {
+            // completed synchronously but the result is still reported
+            // through the OVERLAPPED
+            op.wait(Some(0))?;
+        }
+
+        Ok(op)
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut OVERLAPPED {
+        // OVERLAPPED is Unpin, the pin documents that the address is shared
+        // with the kernel
+        &mut *self.inner as *mut OVERLAPPED
+    }
+
+    /// Wait up to `ms` milliseconds, or forever if `None`, for the operation
+    /// to finish. Returns the number of bytes transferred.
+    ///
+    /// Fails with [`IpcError::Timeout`] if the operation is still pending, in
+    /// which case it can be waited on again.
+    pub fn wait(&mut self, ms: Option<u32>) -> Result<u32, IpcError> {
+        if let State::Done(transferred) = self.state {
+            return Ok(transferred);
+        }
+
+        let mut transferred = 0;
+        let ok = unsafe {
+            match WaitForSingleObject(self.inner.hEvent, ms.unwrap_or(INFINITE)) {
+                WAIT_TIMEOUT => return Err(IpcError::Timeout),
+                // still pending, so dropping it cancels the operation
+                WAIT_FAILED => return Err(IpcError::from_os(GetLastError() as i32)),
+                _ => {}
+            }
+            GetOverlappedResult(self.handle, self.as_mut_ptr(), &mut transferred, 0)
+        };
+
+        if ok == 0 {
+            let e = unsafe { GetLastError() };
+            if e == ERROR_IO_INCOMPLETE {
+                return Err(IpcError::Timeout);
+            }
+            self.state = State::Done(0);
+            return Err(IpcError::from_os(e as i32));
+        }
+
+        self.state = State::Done(transferred);
+        Ok(transferred)
+    }
+}
+
+impl Drop for Overlapped<'_> {
+    fn drop(&mut self) {
+        if self.state == State::Pending {
+            let mut transferred = 0;
+            unsafe {
+                let ow = self.as_mut_ptr();
+                CancelIoEx(self.handle, ow);
+                // the kernel owns `ow` and the buffer until this returns
+                GetOverlappedResult(self.handle, ow, &mut transferred, 1);
+            }
+        }
+    }
+}
@@ -0,0 +1,593 @@
+//! Framing between the capture library and its clients.
+//!
+//! Every frame starts with a fixed size little-endian header:
+//!
+//! | offset | size | field                                         |
+//! |--------|------|-----------------------------------------------|
+//! | 0      | 2    | magic `TA`                                    |
+//! | 2      | 1    | protocol version                              |
+//! | 3      | 1    | [`Kind`]                                      |
+//! | 4      | 1    | [`Backend`]                                   |
+//! | 5      | 1    | flags, see [`FLAG_REPLAYED`]                  |
+//! | 6      | 2    | reserved                                      |
+//! | 8      | 8    | sequence id                                   |
+//! | 16     | 8    | capture timestamp in microseconds since epoch |
+//! | 24     | 4    | payload length                                |
+//!
+//! On connect the client sends a [`Kind::Hello`] frame and the capture library
+//! answers with its own. A peer that sees a different version in a header drops
+//! the connection.
+//!
+//! The client hello may ask for recent messages to be sent again by appending
+//! a NUL, a [`Replay`] mode byte and a little-endian `u64` to its name. The
+//! replayed messages keep their original sequence id and timestamp and are
+//! sent before any new message.
+//!
+//! Payloads larger than a frame are sent as [`Kind::Continuation`] frames
+//! followed by a final frame of the message kind, all with the same sequence id.
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::IpcError;
+use crate::Transport;
+
+pub const MAGIC: [u8; 2] = *b"TA";
+pub const VERSION: u8 = 2;
+pub const HEADER_LEN: usize = 28;
+/// Header flag of frames sent again for a
+/// [`Command::Replay`](crate::control::Command::Replay) instead of live.
+pub const FLAG_REPLAYED: u8 = 1;
+/// Largest payload accepted by [`Decoder`].
+pub const MAX_PAYLOAD: usize = u16::MAX as usize;
+/// Largest payload accepted by [`Reassembler`].
+pub const MAX_MESSAGE: usize = 0x100_0000;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Handshake with the peer name as UTF-8 payload.
+    Hello = 0,
+    /// Captured text as UTF-8 payload.
+    Text = 1,
+    /// Leading part of a payload continued by the next frame.
+    Continuation = 2,
+    /// Braille display text as UTF-8 payload.
+    Braille = 3,
+    /// Speech was interrupted. Has no payload.
+    Stop = 4,
+    /// [`Command`](crate::control::Command) from a client.
+    Request = 5,
+    /// [`Response`](crate::control::Response) to the request with the same
+    /// sequence id.
+    Response = 6,
+}
+
+impl TryFrom<u8> for Kind {
+    type Error = ProtocolError;
+
+    fn try_from(kind: u8) -> Result<Self, ProtocolError> {
+        match kind {
+            0 => Ok(Kind::Hello),
+            1 => Ok(Kind::Text),
+            2 => Ok(Kind::Continuation),
+            3 => Ok(Kind::Braille),
+            4 => Ok(Kind::Stop),
+            5 => Ok(Kind::Request),
+            6 => Ok(Kind::Response),
+            kind => Err(ProtocolError::UnknownKind(kind)),
+        }
+    }
+}
+
+/// Screen reader API an event was captured from.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Backend {
+    #[default]
+    Unknown = 0,
+    Saapi = 1,
+    Nvda = 2,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Unknown => "unknown",
+            Backend::Saapi => "saapi",
+            Backend::Nvda => "nvda",
+        }
+    }
+
+    /// Inverse of [`Backend::name`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Backend::Unknown, Backend::Saapi, Backend::Nvda].into_iter().find(|backend| backend.name() == name)
+    }
+}
+
+impl From<u8> for Backend {
+    fn from(backend: u8) -> Self {
+        match backend {
+            1 => Backend::Saapi,
+            2 => Backend::Nvda,
+            _ => Backend::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ProtocolError {
+    BadMagic([u8; 2]),
+    UnsupportedVersion(u8),
+    UnknownKind(u8),
+    PayloadTooLarge(usize),
+    /// Peer answered the handshake with something other than [`Kind::Hello`].
+    UnexpectedKind(Kind),
+    /// Hello payload has a malformed [`Replay`] request.
+    BadHello,
+    /// Request or response payload is malformed.
+    BadControl,
+    /// Error from the underlying [`Transport`].
+    Transport(IpcError),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::BadMagic(magic) => write!(f, "bad frame magic {magic:?}"),
+            ProtocolError::UnsupportedVersion(v) => write!(f, "peer uses protocol version {v} (expected {VERSION})"),
+            ProtocolError::UnknownKind(kind) => write!(f, "unknown frame kind {kind}"),
+            ProtocolError::PayloadTooLarge(len) => write!(f, "frame payload of {len} bytes exceeds {MAX_PAYLOAD}"),
+            ProtocolError::UnexpectedKind(kind) => write!(f, "expected hello but received {kind:?}"),
+            ProtocolError::BadHello => write!(f, "malformed hello payload"),
+            ProtocolError::BadControl => write!(f, "malformed request or response payload"),
+            ProtocolError::Transport(e) => write!(f, "transport error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// Messages a client asks to be sent again after the handshake.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Replay {
+    #[default]
+    None,
+    /// Every message with a sequence id greater than the given one.
+    Since(u64),
+    /// The given number of most recent messages.
+    Last(u64),
+}
+
+impl Replay {
+    /// Append the mode byte and little-endian argument to `out`.
+    pub(crate) fn encode(self, out: &mut Vec<u8>) {
+        let (mode, arg) = match self {
+            Replay::None => (0, 0),
+            Replay::Since(seq) => (1, seq),
+            Replay::Last(count) => (2, count),
+        };
+        out.push(mode);
+        out.extend_from_slice(&arg.to_le_bytes());
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Option<Self> {
+        let [mode, arg @ ..] = bytes else {
+            return None;
+        };
+        let arg = u64::from_le_bytes(arg.try_into().ok()?);
+        match mode {
+            0 => Some(Replay::None),
+            1 => Some(Replay::Since(arg)),
+            2 => Some(Replay::Last(arg)),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded [`Kind::Hello`] payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hello {
+    pub name: String,
+    pub replay: Replay,
+}
+
+impl Hello {
+    pub fn parse(payload: &[u8]) -> Result<Self, ProtocolError> {
+        let (name, replay) = match payload.iter().position(|b| *b == 0) {
+            Some(i) => (&payload[..i], &payload[i + 1..]),
+            None => (payload, &[][..]),
+        };
+
+        let replay = match replay {
+            [] => Replay::None,
+            replay => Replay::decode(replay).ok_or(ProtocolError::BadHello)?,
+        };
+
+        Ok(Self {
+            name: String::from_utf8_lossy(name).into_owned(),
+            replay,
+        })
+    }
+
+    /// Parse `frame` if it is a [`Kind::Hello`].
+    pub fn from_frame(frame: &Frame) -> Result<Self, ProtocolError> {
+        if frame.kind != Kind::Hello {
+            return Err(ProtocolError::UnexpectedKind(frame.kind));
+        }
+
+        Self::parse(&frame.payload)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.name.as_bytes().to_vec();
+        if self.replay != Replay::None {
+            out.push(0);
+            self.replay.encode(&mut out);
+        }
+        out
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: Kind,
+    pub backend: Backend,
+    pub seq: u64,
+    /// Microseconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Sent again on request, see [`FLAG_REPLAYED`].
+    pub replayed: bool,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(kind: Kind, seq: u64, timestamp: u64, payload: Vec<u8>) -> Self {
+        Self {
+            kind,
+            backend: Backend::Unknown,
+            seq,
+            timestamp,
+            replayed: false,
+            payload,
+        }
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn with_replayed(mut self, replayed: bool) -> Self {
+        self.replayed = replayed;
+        self
+    }
+
+    pub fn hello(hello: &Hello) -> Self {
+        Self::new(Kind::Hello, 0, timestamp(SystemTime::now()), hello.to_bytes())
+    }
+
+    /// Append the encoded frame to `out`.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        debug_assert!(self.payload.len() <= MAX_PAYLOAD);
+        out.reserve(HEADER_LEN + self.payload.len());
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+        out.push(self.kind as u8);
+        out.push(self.backend as u8);
+        out.push(if self.replayed { FLAG_REPLAYED } else { 0 });
+        out.extend_from_slice(&[0; 2]);
+        out.extend_from_slice(&self.seq.to_le_bytes());
+        out.extend_from_slice(&self.timestamp.to_le_bytes());
+        out.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.payload);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+}
+
+/// Append `frame` to `out` split into frames with at most `chunk_size` bytes
+/// of payload each. Returns the number of frames.
+pub fn encode_chunked(frame: &Frame, chunk_size: usize, out: &mut Vec<u8>) -> usize {
+    let chunk_size = chunk_size.clamp(1, MAX_PAYLOAD);
+    if frame.payload.len() <= chunk_size {
+        frame.encode(out);
+        return 1;
+    }
+
+    let mut frames = 0;
+    let mut chunks = frame.payload.chunks(chunk_size).peekable();
+    while let Some(chunk) = chunks.next() {
+        let kind = if chunks.peek().is_some() { Kind::Continuation } else { frame.kind };
+        Frame::new(kind, frame.seq, frame.timestamp, chunk.to_vec())
+            .with_backend(frame.backend)
+            .with_replayed(frame.replayed)
+            .encode(out);
+        frames += 1;
+    }
+
+    frames
+}
+
+/// Set [`FLAG_REPLAYED`] on every frame of the encoded frames in `bytes`.
+pub fn mark_replayed(bytes: &mut [u8]) {
+    let mut i = 0;
+    while i + HEADER_LEN <= bytes.len() {
+        bytes[i + 5] |= FLAG_REPLAYED;
+        let len = u32::from_le_bytes(bytes[i + 24..i + 28].try_into().unwrap()) as usize;
+        i += HEADER_LEN + len;
+    }
+}
+
+/// Joins [`Kind::Continuation`] frames with the final frame of a message.
+#[derive(Default)]
+pub struct Reassembler {
+    partial: Option<Frame>,
+    /// Sequence id of an oversized message whose remaining frames are dropped.
+    discard: Option<u64>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the whole message once its final frame is pushed.
+    ///
+    /// Parts of an unfinished message are discarded when a frame with a
+    /// different sequence id arrives or the message exceeds [`MAX_MESSAGE`].
+    pub fn push(&mut self, frame: Frame) -> Option<Frame> {
+        if self.discard == Some(frame.seq) {
+            if frame.kind != Kind::Continuation {
+                self.discard = None;
+            }
+            return None;
+        }
+        self.discard = None;
+
+        let mut partial = match self.partial.take() {
+            Some(partial) if partial.seq == frame.seq => partial,
+            Some(partial) => {
+                log::warn!("discarding unfinished message #{}", partial.seq);
+                return self.push(frame);
+            }
+            None if frame.kind == Kind::Continuation => {
+                self.partial = Some(frame);
+                return None;
+            }
+            None => return Some(frame),
+        };
+
+        if partial.payload.len() + frame.payload.len() > MAX_MESSAGE {
+            log::warn!("discarding message #{} exceeding {MAX_MESSAGE} bytes", partial.seq);
+            if frame.kind == Kind::Continuation {
+                self.discard = Some(frame.seq);
+            }
+            return None;
+        }
+
+        partial.payload.extend_from_slice(&frame.payload);
+        partial.kind = frame.kind;
+        if partial.kind == Kind::Continuation {
+            self.partial = Some(partial);
+            None
+        } else {
+            Some(partial)
+        }
+    }
+}
+
+/// Convert a [`SystemTime`] to the frame timestamp format.
+pub fn timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+/// Incremental frame decoder for byte streams.
+#[derive(Default)]
+pub struct Decoder {
+    buffer: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Pop the next complete frame.
+    ///
+    /// Errors are not recoverable since the frame boundary is lost.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        if self.buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let header = &self.buffer[..HEADER_LEN];
+        let magic = [header[0], header[1]];
+        if magic != MAGIC {
+            return Err(ProtocolError::BadMagic(magic));
+        }
+        if header[2] != VERSION {
+            return Err(ProtocolError::UnsupportedVersion(header[2]));
+        }
+        let kind = Kind::try_from(header[3])?;
+        let backend = Backend::from(header[4]);
+        let replayed = header[5] & FLAG_REPLAYED != 0;
+        let seq = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let timestamp = u64::from_le_bytes(header[16..24].try_into().unwrap());
+        let len = u32::from_le_bytes(header[24..28].try_into().unwrap()) as usize;
+        if len > MAX_PAYLOAD {
+            return Err(ProtocolError::PayloadTooLarge(len));
+        }
+
+        if self.buffer.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        let payload = self.buffer[HEADER_LEN..HEADER_LEN + len].to_vec();
+        self.buffer.drain(..HEADER_LEN + len);
+
+        Ok(Some(Frame::new(kind, seq, timestamp, payload).with_backend(backend).with_replayed(replayed)))
+    }
+}
+
+pub fn send_frame<T: Transport>(pipe: &mut T, frame: &Frame) -> Result<(), ProtocolError> {
+    pipe.send(&frame.to_bytes())
+        .map(|_| ())
+        .map_err(ProtocolError::Transport)
+}
+
+/// Block until `decoder` has a complete frame, reading from `pipe` as needed.
+pub fn recv_frame<T: Transport>(
+    pipe: &mut T,
+    decoder: &mut Decoder,
+    buffer: &mut [u8],
+) -> Result<Frame, ProtocolError> {
+    loop {
+        if let Some(frame) = decoder.next_frame()? {
+            return Ok(frame);
+        }
+
+        let read = pipe.recv(buffer).map_err(ProtocolError::Transport)?;
+        decoder.push(&buffer[..read as usize]);
+    }
+}
+
+/// Client side of the handshake. Returns the hello sent by the server.
+pub fn handshake_client<T: Transport>(
+    pipe: &mut T,
+    decoder: &mut Decoder,
+    name: &str,
+    replay: Replay,
+) -> Result<Hello, ProtocolError> {
+    send_frame(pipe, &Frame::hello(&Hello { name: name.to_string(), replay }))?;
+
+    let mut buffer = [0; 0x400];
+    let frame = recv_frame(pipe, decoder, &mut buffer)?;
+    Hello::from_frame(&frame)
+}
+
+/// Server side of the handshake. Returns the hello sent by the client.
+///
+/// A client with a different protocol version is still sent a hello so it
+/// can report the mismatch.
+pub fn handshake_server<T: Transport>(
+    pipe: &mut T,
+    decoder: &mut Decoder,
+    name: &str,
+) -> Result<Hello, ProtocolError> {
+    let server = Frame::hello(&Hello { name: name.to_string(), replay: Replay::None });
+    let mut buffer = [0; 0x400];
+    let res = recv_frame(pipe, decoder, &mut buffer);
+    if let Err(ProtocolError::UnsupportedVersion(_)) = &res {
+        let _ = send_frame(pipe, &server);
+    }
+
+    let hello = Hello::from_frame(&res?)?;
+    send_frame(pipe, &server)?;
+    Ok(hello)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(bytes: &[u8]) -> Vec<Frame> {
+        let mut decoder = Decoder::new();
+        let mut frames = Vec::new();
+        // feed byte by byte to cover partial headers and payloads
+        for b in bytes {
+            decoder.push(&[*b]);
+            while let Some(frame) = decoder.next_frame().unwrap() {
+                frames.push(frame);
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn roundtrip() {
+        let frame = Frame::new(Kind::Text, 7, 1234, b"a\0b".to_vec()).with_backend(Backend::Nvda);
+        assert_eq!(decode_all(&frame.to_bytes()), vec![frame]);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let mut bytes = Frame::new(Kind::Text, 1, 0, Vec::new()).to_bytes();
+        bytes[2] = VERSION + 1;
+        let mut decoder = Decoder::new();
+        decoder.push(&bytes);
+        assert_eq!(decoder.next_frame(), Err(ProtocolError::UnsupportedVersion(VERSION + 1)));
+
+        let mut decoder = Decoder::new();
+        decoder.push(b"\0\0");
+        decoder.push(&bytes[2..]);
+        assert_eq!(decoder.next_frame(), Err(ProtocolError::BadMagic([0, 0])));
+    }
+
+    #[test]
+    fn chunked_roundtrip() {
+        let payload = (0..100u8).collect::<Vec<_>>();
+        let first = Frame::new(Kind::Text, 3, 9, payload).with_backend(Backend::Saapi);
+        let second = Frame::new(Kind::Text, 4, 9, Vec::new());
+        let mut bytes = Vec::new();
+        assert_eq!(encode_chunked(&first, 30, &mut bytes), 4);
+        assert_eq!(encode_chunked(&second, 30, &mut bytes), 1);
+
+        let frames = decode_all(&bytes);
+        assert_eq!(frames.len(), 5);
+        assert!(frames[..3].iter().all(|f| f.kind == Kind::Continuation && f.backend == Backend::Saapi));
+
+        let mut reassembler = Reassembler::new();
+        let messages = frames.into_iter()
+            .filter_map(|f| reassembler.push(f))
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec![first, second]);
+    }
+
+    #[test]
+    fn replayed_flag() {
+        let frame = Frame::new(Kind::Text, 3, 9, (0..100u8).collect());
+        let mut bytes = Vec::new();
+        encode_chunked(&frame, 30, &mut bytes);
+        assert!(decode_all(&bytes).iter().all(|f| !f.replayed));
+
+        mark_replayed(&mut bytes);
+        let frames = decode_all(&bytes);
+        assert_eq!(frames.len(), 4);
+        assert!(frames.iter().all(|f| f.replayed));
+
+        let mut reassembler = Reassembler::new();
+        let message = frames.into_iter().find_map(|f| reassembler.push(f));
+        assert_eq!(message, Some(frame.with_replayed(true)));
+    }
+
+    #[test]
+    fn hello_replay() {
+        for replay in [Replay::None, Replay::Since(42), Replay::Last(3)] {
+            let hello = Hello { name: "client@1.0.0".to_string(), replay };
+            assert_eq!(Hello::parse(&hello.to_bytes()), Ok(hello));
+        }
+
+        // hello from a client without replay support
+        assert_eq!(Hello::parse(b"client").unwrap().replay, Replay::None);
+        assert_eq!(Hello::parse(b"client\0\x01\x02"), Err(ProtocolError::BadHello));
+        assert_eq!(Hello::parse(b"client\0\x09\0\0\0\0\0\0\0\0"), Err(ProtocolError::BadHello));
+    }
+
+    #[test]
+    fn reassembler_discards_interrupted() {
+        let mut reassembler = Reassembler::new();
+        assert_eq!(reassembler.push(Frame::new(Kind::Continuation, 1, 0, b"a".to_vec())), None);
+        let next = Frame::new(Kind::Text, 2, 0, b"b".to_vec());
+        assert_eq!(reassembler.push(next.clone()), Some(next));
+    }
+}
@@ -0,0 +1,317 @@
+//! Async pipe types for the `tokio` feature.
+//!
+//! [`AsyncPipe`] and [`AsyncPipeListener`] speak the same byte stream as
+//! [`Pipe`](crate::Pipe) and [`PipeListener`](crate::PipeListener), backed by
+//! tokio's named pipes on Windows and Unix sockets elsewhere. Unlike
+//! [`Listener`](crate::Listener) an [`AsyncPipeListener`] keeps accepting
+//! clients until it is dropped.
+use std::io;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use ::tokio::io::AsyncRead;
+use ::tokio::io::AsyncReadExt;
+use ::tokio::io::AsyncWrite;
+use ::tokio::io::AsyncWriteExt;
+use ::tokio::io::ReadBuf;
+
+#[cfg(windows)]
+use ::tokio::net::windows::named_pipe::ClientOptions;
+#[cfg(windows)]
+use ::tokio::net::windows::named_pipe::NamedPipeClient;
+#[cfg(windows)]
+use ::tokio::net::windows::named_pipe::NamedPipeServer;
+#[cfg(windows)]
+use ::tokio::net::windows::named_pipe::ServerOptions;
+#[cfg(unix)]
+use ::tokio::net::UnixListener;
+#[cfg(unix)]
+use ::tokio::net::UnixStream;
+
+use crate::IpcError;
+use crate::protocol::Decoder;
+use crate::protocol::Frame;
+use crate::protocol::Hello;
+use crate::protocol::ProtocolError;
+use crate::protocol::Replay;
+
+fn strip_address(path: &str) -> Result<&str, IpcError> {
+    match path.strip_suffix('\0') {
+        Some(path) if !path.is_empty() => Ok(path),
+        _ => Err(IpcError::InvalidPath),
+    }
+}
+
+/// Connected byte stream between the capture library and a client.
+pub struct AsyncPipe {
+    inner: Inner,
+}
+
+enum Inner {
+    #[cfg(windows)]
+    Client(NamedPipeClient),
+    #[cfg(windows)]
+    Server(NamedPipeServer),
+    #[cfg(unix)]
+    Stream(UnixStream),
+}
+
+impl AsyncPipe {
+    /// Connect to a NUL-terminated address like
+    /// [`WARTIDE_ADDRESS`](crate::WARTIDE_ADDRESS).
+    pub async fn open(path: &str) -> Result<Self, IpcError> {
+        let path = strip_address(path)?;
+
+        #[cfg(windows)]
+        let inner = Inner::Client(ClientOptions::new().open(path)?);
+        #[cfg(unix)]
+        let inner = Inner::Stream(UnixStream::connect(path).await?);
+
+        Ok(Self {
+            inner,
+        })
+    }
+}
+
+impl AsyncRead for AsyncPipe {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().inner {
+            #[cfg(windows)]
+            Inner::Client(pipe) => Pin::new(pipe).poll_read(cx, buf),
+            #[cfg(windows)]
+            Inner::Server(pipe) => Pin::new(pipe).poll_read(cx, buf),
+            #[cfg(unix)]
+            Inner::Stream(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for AsyncPipe {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().inner {
+            #[cfg(windows)]
+            Inner::Client(pipe) => Pin::new(pipe).poll_write(cx, buf),
+            #[cfg(windows)]
+            Inner::Server(pipe) => Pin::new(pipe).poll_write(cx, buf),
+            #[cfg(unix)]
+            Inner::Stream(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().inner {
+            #[cfg(windows)]
+            Inner::Client(pipe) => Pin::new(pipe).poll_flush(cx),
+            #[cfg(windows)]
+            Inner::Server(pipe) => Pin::new(pipe).poll_flush(cx),
+            #[cfg(unix)]
+            Inner::Stream(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().inner {
+            #[cfg(windows)]
+            Inner::Client(pipe) => Pin::new(pipe).poll_shutdown(cx),
+            #[cfg(windows)]
+            Inner::Server(pipe) => Pin::new(pipe).poll_shutdown(cx),
+            #[cfg(unix)]
+            Inner::Stream(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Server side of an [`AsyncPipe`].
+pub struct AsyncPipeListener {
+    #[cfg(windows)]
+    next: NamedPipeServer,
+    #[cfg(windows)]
+    path: String,
+    #[cfg(unix)]
+    inner: UnixListener,
+    #[cfg(unix)]
+    _file: crate::unix::SocketFile,
+}
+
+impl AsyncPipeListener {
+    /// Bind to a NUL-terminated address like
+    /// [`WARTIDE_ADDRESS`](crate::WARTIDE_ADDRESS).
+    ///
+    /// Needs to be called from within a tokio runtime.
+    pub fn bind(path: &str) -> Result<Self, IpcError> {
+        #[cfg(windows)]
+        {
+            let path = strip_address(path)?;
+            if path.len() >= 1000 {
+                return Err(IpcError::InvalidPath);
+            }
+
+            Ok(Self {
+                next: Self::create(path)?,
+                path: path.to_string(),
+            })
+        }
+
+        #[cfg(unix)]
+        {
+            strip_address(path)?;
+            let (listener, file) = crate::unix::bind(path)?;
+            listener.set_nonblocking(true)?;
+            Ok(Self {
+                inner: UnixListener::from_std(listener)?,
+                _file: file,
+            })
+        }
+    }
+
+    #[cfg(windows)]
+    fn create(path: &str) -> Result<NamedPipeServer, IpcError> {
+        Ok(ServerOptions::new()
+            .reject_remote_clients(true)
+            .out_buffer_size(2048)
+            .in_buffer_size(0)
+            .create(path)?)
+    }
+
+    /// Wait for the next client to connect.
+    pub async fn accept(&mut self) -> Result<AsyncPipe, IpcError> {
+        #[cfg(windows)]
+        {
+            self.next.connect().await?;
+            // create the next instance before handing out this one so clients
+            // never see the address missing
+            let next = Self::create(&self.path)?;
+            let server = std::mem::replace(&mut self.next, next);
+            Ok(AsyncPipe {
+                inner: Inner::Server(server),
+            })
+        }
+
+        #[cfg(unix)]
+        {
+            let (stream, _) = self.inner.accept().await?;
+            Ok(AsyncPipe {
+                inner: Inner::Stream(stream),
+            })
+        }
+    }
+}
+
+/// Async [`protocol::send_frame`](crate::protocol::send_frame).
+pub async fn send_frame<T: AsyncWrite + Unpin>(
+    pipe: &mut T,
+    frame: &Frame,
+) -> Result<(), ProtocolError> {
+    pipe.write_all(&frame.to_bytes())
+        .await
+        .map_err(|e| ProtocolError::Transport(e.into()))
+}
+
+/// Async [`protocol::recv_frame`](crate::protocol::recv_frame).
+pub async fn recv_frame<T: AsyncRead + Unpin>(
+    pipe: &mut T,
+    decoder: &mut Decoder,
+    buffer: &mut [u8],
+) -> Result<Frame, ProtocolError> {
+    loop {
+        if let Some(frame) = decoder.next_frame()? {
+            return Ok(frame);
+        }
+
+        let read = pipe.read(buffer)
+            .await
+            .map_err(|e| ProtocolError::Transport(e.into()))?;
+        if read == 0 && !buffer.is_empty() {
+            return Err(ProtocolError::Transport(IpcError::Disconnected));
+        }
+        decoder.push(&buffer[..read]);
+    }
+}
+
+/// Async [`protocol::handshake_client`](crate::protocol::handshake_client).
+pub async fn handshake_client<T: AsyncRead + AsyncWrite + Unpin>(
+    pipe: &mut T,
+    decoder: &mut Decoder,
+    name: &str,
+    replay: Replay,
+) -> Result<Hello, ProtocolError> {
+    send_frame(pipe, &Frame::hello(&Hello { name: name.to_string(), replay })).await?;
+
+    let mut buffer = [0; 0x400];
+    let frame = recv_frame(pipe, decoder, &mut buffer).await?;
+    Hello::from_frame(&frame)
+}
+
+/// Async [`protocol::handshake_server`](crate::protocol::handshake_server).
+pub async fn handshake_server<T: AsyncRead + AsyncWrite + Unpin>(
+    pipe: &mut T,
+    decoder: &mut Decoder,
+    name: &str,
+) -> Result<Hello, ProtocolError> {
+    let server = Frame::hello(&Hello { name: name.to_string(), replay: Replay::None });
+    let mut buffer = [0; 0x400];
+    let res = recv_frame(pipe, decoder, &mut buffer).await;
+    if let Err(ProtocolError::UnsupportedVersion(_)) = &res {
+        let _ = send_frame(pipe, &server).await;
+    }
+
+    let hello = Hello::from_frame(&res?)?;
+    send_frame(pipe, &server).await?;
+    Ok(hello)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::protocol::Kind;
+
+    #[::tokio::test]
+    async fn handshake_and_frames() {
+        let path = format!("/tmp/net.wartide.d4.tts-air-test-tokio-{}\0", std::process::id());
+        let mut listener = AsyncPipeListener::bind(&path).unwrap();
+
+        let server = async {
+            // accepts more than one client
+            for i in 0..2u64 {
+                let mut pipe = listener.accept().await.unwrap();
+                let mut decoder = Decoder::new();
+                let hello = handshake_server(&mut pipe, &mut decoder, "server").await.unwrap();
+                assert_eq!(hello.replay, Replay::Since(i));
+                let frame = Frame::new(Kind::Text, i, 0, hello.name.into_bytes());
+                send_frame(&mut pipe, &frame).await.unwrap();
+            }
+        };
+
+        let clients = async {
+            for i in 0..2u64 {
+                let mut pipe = AsyncPipe::open(&path).await.unwrap();
+                let mut decoder = Decoder::new();
+                let hello = handshake_client(&mut pipe, &mut decoder, "client", Replay::Since(i))
+                    .await
+                    .unwrap();
+                assert_eq!(hello.name, "server");
+
+                let mut buffer = [0; 0x100];
+                let frame = recv_frame(&mut pipe, &mut decoder, &mut buffer).await.unwrap();
+                assert_eq!((frame.seq, &frame.payload[..]), (i, &b"client"[..]));
+
+                // server side is dropped after its frame
+                let res = recv_frame(&mut pipe, &mut decoder, &mut buffer).await;
+                assert!(matches!(res, Err(ProtocolError::Transport(IpcError::Disconnected))));
+            }
+        };
+
+        ::tokio::join!(server, clients);
+        drop(listener);
+        assert!(!std::path::Path::new(path.trim_end_matches('\0')).exists());
+    }
+}
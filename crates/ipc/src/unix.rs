@@ -0,0 +1,269 @@
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::IpcError;
+use crate::Listener;
+use crate::Transport;
+
+/// Socket file of a bound listener, removed along with its lock file on
+/// drop.
+///
+/// Socket files outlive processes that exit without cleanup. The lock on the
+/// file next to it tells a stale socket from a live one without connecting,
+/// which the listener would take as a client.
+pub(crate) struct SocketFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    _lock: File,
+}
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        // removed while still locked so a new listener can't lock the file
+        // about to be removed
+        let _ = std::fs::remove_file(&self.path);
+        let _ = std::fs::remove_file(&self.lock_path);
+    }
+}
+
+/// Bind `path`, taking over the socket file left behind by a dead process.
+pub(crate) fn bind(path: &str) -> Result<(UnixListener, SocketFile), IpcError> {
+    let path = path.trim_end_matches('\0');
+    if path.is_empty() || path.len() >= 1000 {
+        return Err(IpcError::InvalidPath);
+    }
+
+    let lock_path = PathBuf::from(format!("{path}.lock"));
+    let lock = lock(&lock_path)?;
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            // nothing else holds the lock so the socket file is stale
+            let _ = std::fs::remove_file(path);
+            UnixListener::bind(path)?
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok((listener, SocketFile {
+        path: path.into(),
+        lock_path,
+        _lock: lock,
+    }))
+}
+
+/// Lock the file at `path`, failing with `EADDRINUSE` if a listener holds
+/// it. The lock is released when the file is closed, even by a crash.
+fn lock(path: &Path) -> Result<File, IpcError> {
+    loop {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::EWOULDBLOCK) {
+                return Err(IpcError::from_os(libc::EADDRINUSE));
+            }
+            return Err(e.into());
+        }
+
+        // the previous listener may have removed the file before it was
+        // locked here, in which case the lock guards nothing
+        let locked = file.metadata()?;
+        if std::fs::metadata(path).is_ok_and(|m| (m.dev(), m.ino()) == (locked.dev(), locked.ino())) {
+            return Ok(file);
+        }
+    }
+}
+
+pub struct UnixSocketListener {
+    inner: Option<(UnixListener, SocketFile)>,
+}
+
+impl Listener for UnixSocketListener {
+    type Transport = UnixSocket;
+
+    fn bind(path: &str) -> Result<Self, IpcError> {
+        Ok(Self {
+            inner: Some(bind(path)?),
+        })
+    }
+
+    fn listen(&mut self) -> Result<UnixSocket, IpcError> {
+        self.listen_timeout_ms_(None)
+    }
+
+    fn listen_timeout_ms(&mut self, ms: u32) -> Result<UnixSocket, IpcError> {
+        self.listen_timeout_ms_(Some(ms))
+    }
+}
+
+impl UnixSocketListener {
+    fn listen_timeout_ms_(&mut self, ms: Option<u32>) -> Result<UnixSocket, IpcError> {
+        let Some((listener, _)) = &self.inner else {
+            log::warn!("UnixSocketListener::inner is None");
+            return Err(IpcError::InvalidHandle);
+        };
+
+        if let Some(ms) = ms {
+            let mut fd = libc::pollfd {
+                fd: listener.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+
+            let ready = unsafe {
+                libc::poll(&mut fd, 1, ms.min(i32::MAX as u32) as i32)
+            };
+
+            if ready < 0 {
+                return Err(IpcError::last_os_error());
+            } else if ready == 0 {
+                return Err(IpcError::Timeout);
+            }
+        }
+
+        let (inner, _) = listener.accept()?;
+
+        // match named pipe semantics where a listener is spent on connect
+        self.inner = None;
+
+        Ok(UnixSocket {
+            inner,
+        })
+    }
+}
+
+pub struct UnixSocket {
+    inner: UnixStream,
+}
+
+impl Transport for UnixSocket {
+    fn open(path: &str) -> Result<Self, IpcError> {
+        let Some(path) = path.strip_suffix('\0') else {
+            return Err(IpcError::InvalidPath);
+        };
+
+        Ok(UnixSocket {
+            inner: UnixStream::connect(path)?,
+        })
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> Result<u64, IpcError> {
+        loop {
+            match self.inner.read(buffer) {
+                // named pipes report a closed peer as an error instead of EOF
+                Ok(0) if !buffer.is_empty() => return Err(IpcError::Disconnected),
+                Ok(read) => return Ok(read as u64),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn send(&mut self, msg: &[u8]) -> Result<u64, IpcError> {
+        self.inner.write_all(msg)?;
+        Ok(msg.len() as u64)
+    }
+
+    fn try_clone(&self) -> Result<Self, IpcError> {
+        Ok(UnixSocket {
+            inner: self.inner.try_clone()?,
+        })
+    }
+
+    fn shutdown(&self) -> Result<(), IpcError> {
+        match self.inner.shutdown(std::net::Shutdown::Both) {
+            // the peer already closed the connection
+            Err(e) if e.kind() == io::ErrorKind::NotConnected => Ok(()),
+            res => Ok(res?),
+        }
+    }
+
+    fn discover(prefix: &str) -> Vec<String> {
+        let prefix = Path::new(prefix);
+        let (Some(dir), Some(name)) = (prefix.parent(), prefix.file_name()) else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_socket()))
+            .filter(|entry| entry.file_name().as_bytes().starts_with(name.as_bytes()))
+            .filter_map(|entry| Some(format!("{}\0", entry.path().to_str()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_handle_closes() {
+        let address = format!("/tmp/tts-air-unix-test-{}\0", std::process::id());
+        let mut listener = UnixSocketListener::bind(&address).unwrap();
+        let mut client = UnixSocket::open(&address).unwrap();
+        let server = listener.listen().unwrap();
+
+        // dropping one handle keeps the connection open for the others
+        let mut clone = server.try_clone().unwrap();
+        drop(server);
+        client.send(b"ping").unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(clone.recv(&mut buffer), Ok(4));
+
+        drop(clone);
+        assert_eq!(client.recv(&mut buffer), Err(IpcError::Disconnected));
+    }
+
+    #[test]
+    fn shutdown_closes_every_handle() {
+        let address = format!("/tmp/tts-air-unix-shutdown-test-{}\0", std::process::id());
+        let mut listener = UnixSocketListener::bind(&address).unwrap();
+        let mut client = UnixSocket::open(&address).unwrap();
+        let server = listener.listen().unwrap();
+
+        let mut clone = server.try_clone().unwrap();
+        server.shutdown().unwrap();
+        let mut buffer = [0; 4];
+        assert_eq!(clone.recv(&mut buffer), Err(IpcError::Disconnected));
+        assert_eq!(clone.send(b"ping"), Err(IpcError::Disconnected));
+        assert_eq!(client.recv(&mut buffer), Err(IpcError::Disconnected));
+    }
+
+    #[test]
+    fn bind_without_connecting() {
+        let address = format!("/tmp/tts-air-unix-bind-test-{}\0", std::process::id());
+        let path = address.trim_end_matches('\0');
+
+        // a live listener is left alone
+        let mut listener = UnixSocketListener::bind(&address).unwrap();
+        assert_eq!(UnixSocketListener::bind(&address).err(), Some(IpcError::from_os(libc::EADDRINUSE)));
+        assert_eq!(listener.listen_timeout_ms(0).err(), Some(IpcError::Timeout));
+        drop(listener);
+        assert!(!Path::new(path).exists());
+
+        // a socket file without a lock, as left behind by a process that
+        // exited, is taken over
+        std::mem::forget(UnixListener::bind(path).unwrap());
+        let mut listener = UnixSocketListener::bind(&address).unwrap();
+        let _client = UnixSocket::open(&address).unwrap();
+        listener.listen_timeout_ms(1000).unwrap();
+    }
+}
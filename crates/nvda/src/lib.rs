@@ -0,0 +1,34 @@
+use std::sync::atomic::AtomicBool;
+
+use tts_air_capture::Event;
+use tts_air_capture::catch_panic;
+
+tts_air_capture::entry_point!(tts_air_capture::Backend::Nvda);
+
+// NVDA controller functions return a Win32 error code where 0 is success
+
+#[no_mangle]
+unsafe extern "system" fn nvdaController_testIfRunning() -> u32 {
+    0
+}
+
+#[no_mangle]
+unsafe extern "system" fn nvdaController_speakText(wchar: *const u16) -> u32 {
+    static DID_PANIC: AtomicBool = AtomicBool::new(false);
+    catch_panic(&DID_PANIC, || tts_air_capture::send_wchar(wchar, Event::Say));
+    0
+}
+
+#[no_mangle]
+unsafe extern "system" fn nvdaController_cancelSpeech() -> u32 {
+    static DID_PANIC: AtomicBool = AtomicBool::new(false);
+    catch_panic(&DID_PANIC, || tts_air_capture::send_event(Event::Stop));
+    0
+}
+
+#[no_mangle]
+unsafe extern "system" fn nvdaController_brailleMessage(wchar: *const u16) -> u32 {
+    static DID_PANIC: AtomicBool = AtomicBool::new(false);
+    catch_panic(&DID_PANIC, || tts_air_capture::send_wchar(wchar, Event::Braille));
+    0
+}
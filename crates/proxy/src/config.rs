@@ -0,0 +1,189 @@
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
+use std::net::SocketAddrV4;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::origin::OriginPattern;
+
+/// Name of the optional config file next to the proxy executable.
+pub const FILE_NAME: &str = "tts_air_proxy.toml";
+
+/// Comma-separated listen addresses that take precedence over the config file.
+pub const LISTEN_ENV: &str = "TTS_AIR_LISTEN";
+
+/// Upper bound of [`Config::history`].
+const MAX_HISTORY: usize = 0x10000;
+
+const DEFAULT_LISTEN: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 61806));
+
+/// Settings of the proxy read from [`FILE_NAME`].
+///
+/// Every field is optional in the file:
+///
+/// ```toml
+/// listen = ["127.0.0.1:61806", "[::1]:61806"]
+/// origins = ["https://d4.wartide.net", "null", "http://localhost:*"]
+/// history = 256
+/// filters = "tts_air_filters.toml"
+/// record = "recorded"
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Addresses the WebSocket server listens on.
+    pub listen: Vec<SocketAddr>,
+    /// Pages allowed to connect to the WebSocket server.
+    pub origins: Vec<OriginPattern>,
+    /// Number of recent events kept for clients that reconnect.
+    pub history: usize,
+    /// Item filter rules, relative to the config file, see [`filter`](crate::filter).
+    pub filters: PathBuf,
+    /// Directory item tooltips are recorded to as test fixtures, relative to
+    /// the config file, see [`item::record`](crate::item::record).
+    pub record: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: vec![DEFAULT_LISTEN],
+            origins: vec![
+                "https://d4.wartide.net".parse().unwrap(),
+                // local files
+                OriginPattern::Null,
+            ],
+            history: 256,
+            filters: PathBuf::from(crate::filter::FILE_NAME),
+            record: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    Parse(String),
+    Invalid(&'static str, String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Parse(e) => write!(f, "{e}"),
+            ConfigError::Invalid(field, reason) => write!(f, "invalid `{field}`: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read the config file in `dir`. A missing file gives the defaults.
+    pub fn load(dir: &Path) -> Result<Self, ConfigError> {
+        let path = dir.join(FILE_NAME);
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(ConfigError::Parse(format!("failed to read {path:?}: {e}"))),
+        };
+        config.filters = dir.join(&config.filters);
+        config.record = config.record.map(|record| dir.join(record));
+        Ok(config)
+    }
+
+    /// Replace [`Config::listen`] with the addresses in `list` from the
+    /// setting `field`, such as [`LISTEN_ENV`].
+    pub fn set_listen(&mut self, field: &'static str, list: &str) -> Result<(), ConfigError> {
+        self.listen = list
+            .split(',')
+            .map(|addr| addr.trim().parse().map_err(|e| {
+                ConfigError::Invalid(field, format!("{addr:?} is not an address like 127.0.0.1:61806 ({e})"))
+            }))
+            .collect::<Result<_, _>>()?;
+        self.validate()
+    }
+
+    /// Allow `pattern` from the setting `field` in addition to
+    /// [`Config::origins`].
+    pub fn allow_origin(&mut self, field: &'static str, pattern: &str) -> Result<(), ConfigError> {
+        let pattern = pattern.parse().map_err(|e| ConfigError::Invalid(field, e))?;
+        self.origins.push(pattern);
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.listen.is_empty() {
+            return Err(ConfigError::Invalid("listen", "needs at least one address".to_string()));
+        }
+        if self.origins.is_empty() {
+            return Err(ConfigError::Invalid("origins", "needs at least one origin".to_string()));
+        }
+        // the proxy needs the latest event to route replays and parse items
+        if self.history == 0 || self.history > MAX_HISTORY {
+            return Err(ConfigError::Invalid("history", format!("needs to be between 1 and {MAX_HISTORY}")));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_is_default() {
+        assert_eq!(Config::parse(""), Ok(Config::default()));
+        assert_eq!(Config::default().listen, vec!["127.0.0.1:61806".parse().unwrap()]);
+        assert!(crate::origin::allows(&Config::default().origins, "https://d4.wartide.net"));
+        assert!(crate::origin::allows(&Config::default().origins, "null"));
+    }
+
+    #[test]
+    fn listen_addresses() {
+        let config = Config::parse(r#"listen = ["127.0.0.1:8080", "[::1]:8080"]"#).unwrap();
+        assert_eq!(config.listen.len(), 2);
+        assert!(config.listen[1].is_ipv6());
+
+        let mut config = Config::default();
+        config.set_listen(LISTEN_ENV, "[::1]:1, 127.0.0.1:2").unwrap();
+        assert_eq!(config.listen, vec!["[::1]:1".parse().unwrap(), "127.0.0.1:2".parse().unwrap()]);
+    }
+
+    #[test]
+    fn origins() {
+        let mut config = Config::parse(r#"origins = ["http://localhost:*"]"#).unwrap();
+        assert!(crate::origin::allows(&config.origins, "http://localhost:3000"));
+        assert!(!crate::origin::allows(&config.origins, "null"));
+
+        config.allow_origin("--allow-origin", "https://*.example.com").unwrap();
+        assert!(crate::origin::allows(&config.origins, "https://overlay.example.com"));
+        assert!(crate::origin::allows(&config.origins, "http://localhost:3000"));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(matches!(Config::parse("listen = '127.0.0.1:1'"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("listen = ['localhost']"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("lisen = []"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("listen = []"), Err(ConfigError::Invalid("listen", _))));
+        assert!(matches!(Config::parse("origins = []"), Err(ConfigError::Invalid("origins", _))));
+        assert!(matches!(Config::parse("history = 1000000"), Err(ConfigError::Invalid("history", _))));
+        assert!(matches!(Config::parse("history = 0"), Err(ConfigError::Invalid("history", _))));
+        assert!(matches!(Config::parse("filters = 1"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("record = 1"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("origins = ['https://*d4.net']"), Err(ConfigError::Parse(_))));
+
+        let mut config = Config::default();
+        assert!(matches!(config.set_listen("--listen", "127.0.0.1"), Err(ConfigError::Invalid("--listen", _))));
+        assert!(matches!(config.set_listen("--listen", ""), Err(ConfigError::Invalid("--listen", _))));
+        assert!(matches!(config.allow_origin("--allow-origin", "localhost"), Err(ConfigError::Invalid(_, _))));
+    }
+}
@@ -0,0 +1,331 @@
+//! Rules matched against parsed items.
+//!
+//! The rules file has a `[[rule]]` table per rule with a `name` and a `when`
+//! condition:
+//!
+//! ```toml
+//! [[rule]]
+//! name = "Ancestral cooldown helm"
+//! when = { all = [
+//!     { rarity = ["legendary", "unique", "mythic"] },
+//!     { item_power = { min = 900 } },
+//!     { affix = { stat = "cooldown_reduction", min = 9.0 } },
+//!     { not = { quality = ["sacred"] } },
+//! ] }
+//! ```
+//!
+//! An affix `stat` is a stat id or, for stats without one, the stat with its
+//! numbers replaced by `#`.
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::config::ConfigError;
+use crate::item::Item;
+use crate::item::Quality;
+use crate::item::Rarity;
+
+/// Default name of the rules file next to the proxy executable.
+pub const FILE_NAME: &str = "tts_air_filters.toml";
+
+/// How often the rules file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<Rule>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    pub when: Condition,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    /// Any of the rarities.
+    Rarity(Vec<Rarity>),
+    /// Any of the qualities.
+    Quality(Vec<Quality>),
+    ItemPower(Bounds),
+    /// An affix with a value within the bounds.
+    Affix(AffixCondition),
+}
+
+/// Inclusive bounds, either can be left out.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bounds {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AffixCondition {
+    pub stat: String,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl AffixCondition {
+    fn bounds(&self) -> Bounds {
+        Bounds { min: self.min, max: self.max }
+    }
+}
+
+impl Bounds {
+    fn contains(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+impl Condition {
+    /// Whether `item` matches, adding the terms that made it match to `terms`.
+    fn eval(&self, item: &Item, terms: &mut Vec<serde_json::Value>) -> bool {
+        let len = terms.len();
+        let matches = match self {
+            Condition::All(conditions) => conditions.iter().all(|c| c.eval(item, terms)),
+            Condition::Any(conditions) => {
+                // every matching branch is a reason for the match
+                conditions.iter().fold(false, |any, c| c.eval(item, terms) | any)
+            }
+            Condition::Not(condition) => !condition.eval(item, &mut Vec::new()),
+            Condition::Rarity(rarities) => rarities.contains(&item.rarity)
+                && push(terms, json!({"rarity": item.rarity})),
+            Condition::Quality(qualities) => item.quality.is_some_and(|q| qualities.contains(&q))
+                && push(terms, json!({"quality": item.quality})),
+            Condition::ItemPower(bounds) => item.item_power.is_some_and(|power| bounds.contains(power.into()))
+                && push(terms, json!({"item_power": item.item_power})),
+            Condition::Affix(condition) => {
+                let affix = item.affixes.iter().find(|affix| {
+                    let stat_matches = affix.id == Some(condition.stat.as_str())
+                        || affix.stat.eq_ignore_ascii_case(&condition.stat);
+                    let value = affix.rolls.first().map(|roll| roll.value);
+                    // presence alone doesn't need a value
+                    stat_matches && (condition.bounds() == Bounds::default() || value.is_some_and(|v| condition.bounds().contains(v)))
+                });
+                affix.is_some_and(|affix| push(terms, json!({
+                    "affix": condition.stat,
+                    "text": affix.text,
+                    "value": affix.rolls.first().map(|roll| roll.value),
+                })))
+            }
+        };
+        if !matches {
+            terms.truncate(len);
+        }
+        matches
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Condition::All(conditions) | Condition::Any(conditions) => {
+                conditions.iter().try_for_each(Condition::validate)
+            }
+            Condition::Not(condition) => condition.validate(),
+            Condition::Rarity(list) if list.is_empty() => Err("`rarity` needs at least one rarity".to_string()),
+            Condition::Quality(list) if list.is_empty() => Err("`quality` needs at least one quality".to_string()),
+            Condition::ItemPower(Bounds { min: Some(min), max: Some(max) })
+            | Condition::Affix(AffixCondition { min: Some(min), max: Some(max), .. }) if min > max => {
+                Err("`min` is above `max`".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn push(terms: &mut Vec<serde_json::Value>, term: serde_json::Value) -> bool {
+    terms.push(term);
+    true
+}
+
+/// A rule that matched an item.
+pub struct Match<'a> {
+    pub rule: &'a str,
+    pub terms: Vec<serde_json::Value>,
+}
+
+pub fn parse(text: &str) -> Result<Vec<Rule>, ConfigError> {
+    let file: RulesFile = toml::from_str(text).map_err(|e| ConfigError::Parse(e.to_string()))?;
+    for rule in &file.rule {
+        if rule.name.is_empty() {
+            return Err(ConfigError::Invalid("name", "rules need a name".to_string()));
+        }
+        rule.when.validate().map_err(|e| ConfigError::Invalid("when", format!("rule {:?}: {e}", rule.name)))?;
+    }
+    Ok(file.rule)
+}
+
+/// Rules of a file that is read again when it changes.
+pub struct Filters {
+    path: PathBuf,
+    rules: Vec<Rule>,
+    /// Modification time and length of the file when it was read.
+    version: Option<(SystemTime, u64)>,
+    checked: Option<Instant>,
+}
+
+impl Filters {
+    pub fn new(path: PathBuf) -> Self {
+        let mut filters = Self {
+            path,
+            rules: Vec::new(),
+            version: None,
+            checked: None,
+        };
+        filters.reload();
+        filters
+    }
+
+    /// Read the file again if it changed since the last check, keeping the
+    /// previous rules if it is invalid.
+    pub fn reload(&mut self) {
+        if self.checked.is_some_and(|checked| checked.elapsed() < RELOAD_INTERVAL) {
+            return;
+        }
+        self.checked = Some(Instant::now());
+
+        let version = std::fs::metadata(&self.path).ok().and_then(|m| Some((m.modified().ok()?, m.len())));
+        if version == self.version {
+            return;
+        }
+        self.version = version;
+
+        if version.is_none() {
+            if !self.rules.is_empty() {
+                log::info!("filter rules {:?} removed", self.path);
+            }
+            self.rules.clear();
+            return;
+        }
+        let rules = std::fs::read_to_string(&self.path)
+            .map_err(|e| ConfigError::Parse(e.to_string()))
+            .and_then(|text| parse(&text));
+        match rules {
+            Ok(rules) => {
+                log::info!("loaded {} filter rules from {:?}", rules.len(), self.path);
+                self.rules = rules;
+            }
+            Err(e) => log::error!("failed to load filter rules {:?}: {e}", self.path),
+        }
+    }
+
+    /// Rules matching `item` in the order of the file.
+    pub fn matches(&self, item: &Item) -> Vec<Match<'_>> {
+        self.rules.iter()
+            .filter_map(|rule| {
+                let mut terms = Vec::new();
+                rule.when.eval(item, &mut terms).then_some(Match { rule: &rule.name, terms })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: &str = r#"
+        [[rule]]
+        name = "ancestral cooldown"
+        when = { all = [
+            { rarity = ["legendary", "unique"] },
+            { quality = ["ancestral"] },
+            { item_power = { min = 900 } },
+            { any = [
+                { affix = { stat = "cooldown_reduction", min = 10.0 } },
+                { affix = { stat = "+# Maximum Life" } },
+                { affix = { stat = "attack_speed" } },
+            ] },
+        ] }
+
+        [[rule]]
+        name = "not sacred"
+        when = { not = { quality = ["sacred"] } }
+
+        [[rule]]
+        name = "low cooldown"
+        when = { affix = { stat = "cooldown_reduction", max = 10.0 } }
+    "#;
+
+    fn names(filters: &Filters, item: &Item) -> Vec<String> {
+        filters.matches(item).into_iter().map(|m| m.rule.to_string()).collect()
+    }
+
+    #[test]
+    fn conditions() {
+        let mut filters = Filters::new(PathBuf::from("missing.toml"));
+        filters.rules = parse(RULES).unwrap();
+
+        let helm = crate::item::parse(include_str!("../fixtures/items/synthetic/legendary_helm.txt")).unwrap();
+        let m = filters.matches(&helm).remove(0);
+        assert_eq!(m.rule, "ancestral cooldown");
+        assert_eq!(m.terms, vec![
+            json!({"rarity": "legendary"}),
+            json!({"quality": "ancestral"}),
+            json!({"item_power": 925}),
+            json!({"affix": "cooldown_reduction", "text": "+10.5% Cooldown Reduction", "value": 10.5}),
+            json!({"affix": "+# Maximum Life", "text": "+142 Maximum Life", "value": 142.0}),
+        ]);
+        assert_eq!(names(&filters, &helm), ["ancestral cooldown", "not sacred"]);
+
+        let pants = crate::item::parse(include_str!("../fixtures/items/synthetic/unique_pants.txt")).unwrap();
+        assert!(names(&filters, &pants).is_empty());
+        let ring = crate::item::parse(include_str!("../fixtures/items/synthetic/magic_ring.txt")).unwrap();
+        assert_eq!(names(&filters, &ring), ["not sacred"]);
+        let amulet = crate::item::parse(include_str!("../fixtures/items/synthetic/imprinted_amulet.txt")).unwrap();
+        assert_eq!(names(&filters, &amulet), ["not sacred", "low cooldown"]);
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert!(matches!(parse("[[rule]]\nname = 'x'"), Err(ConfigError::Parse(_))));
+        assert!(matches!(parse("[[rule]]\nname = 'x'\nwhen = { rarity = ['epic'] }"), Err(ConfigError::Parse(_))));
+        assert!(matches!(parse("[[rule]]\nname = 'x'\nwhen = { all = [], any = [] }"), Err(ConfigError::Parse(_))));
+        let text = "[[rule]]\nname = 'x'\nwhen = { item_power = { min = 2, max = 1 } }";
+        assert!(matches!(parse(text), Err(ConfigError::Invalid("when", _))));
+        assert!(matches!(parse("[[rule]]\nname = ''\nwhen = { rarity = ['rare'] }"), Err(ConfigError::Invalid("name", _))));
+        assert_eq!(parse("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn reload() {
+        let path = std::env::temp_dir().join(format!("tts_air_filters_reload_{}.toml", std::process::id()));
+        std::fs::write(&path, "[[rule]]\nname = 'legendary'\nwhen = { rarity = ['legendary'] }").unwrap();
+        let mut filters = Filters::new(path.clone());
+        let helm = crate::item::parse(include_str!("../fixtures/items/synthetic/legendary_helm.txt")).unwrap();
+        assert_eq!(names(&filters, &helm), ["legendary"]);
+
+        // checks are throttled
+        std::fs::write(&path, "[[rule]]\nname = 'ancestral'\nwhen = { quality = ['ancestral'] }").unwrap();
+        filters.reload();
+        assert_eq!(names(&filters, &helm), ["legendary"]);
+        filters.checked = None;
+        filters.reload();
+        assert_eq!(names(&filters, &helm), ["ancestral"]);
+
+        // an invalid file keeps the previous rules
+        std::fs::write(&path, "[[rule]]\nname = 'x'").unwrap();
+        filters.checked = None;
+        filters.reload();
+        assert_eq!(names(&filters, &helm), ["ancestral"]);
+
+        std::fs::remove_file(&path).unwrap();
+        filters.checked = None;
+        filters.reload();
+        assert!(names(&filters, &helm).is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "de",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("heilig", Quality::Sacred),
+        ("uralt", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mythisch", Rarity::Mythic),
+        ("gewöhnlich", Rarity::Common),
+        ("magisch", Rarity::Magic),
+        ("selten", Rarity::Rare),
+        ("legendär", Rarity::Legendary),
+        ("einzigartig", Rarity::Unique),
+    ],
+    item_power: "Gegenstandsmacht",
+    armor: "Rüstung",
+    damage_per_second: "Schaden pro Sekunde",
+    damage_per_hit: "Schaden pro Treffer",
+    attacks_per_second: "Angriffe pro Sekunde",
+    empty_socket: "Leere Fassung",
+    gems: &["Rubin", "Saphir", "Smaragd", "Diamant", "Topas", "Amethyst", "Schädel"],
+    imprinted: "Eingeprägt:",
+    ignored: &[
+        "Benötigt Stufe",
+        "Verkaufswert",
+        "Haltbarkeit",
+        "Verbesserungen",
+        "Accountgebunden",
+        "Einzigartige Ausrüstung",
+        "Eigenschaften, die beim Anlegen verloren gehen",
+        "Härtungen",
+        "Meisterarbeit",
+    ],
+    stats: &[
+        ("+# Stärke", "strength"),
+        ("+# Geschicklichkeit", "dexterity"),
+        ("+# Intelligenz", "intelligence"),
+        ("+# Willenskraft", "willpower"),
+        ("+# Alle Werte", "all_stats"),
+        ("+# Maximales Leben", "maximum_life"),
+        ("+# Rüstung", "armor"),
+        ("+#% Gesamtrüstung", "total_armor"),
+        ("+#% Kritische Trefferchance", "critical_strike_chance"),
+        ("+#% Kritischer Trefferschaden", "critical_strike_damage"),
+        ("+#% Verwundbarkeitsschaden", "vulnerable_damage"),
+        ("+#% Angriffsgeschwindigkeit", "attack_speed"),
+        ("+#% Bewegungsgeschwindigkeit", "movement_speed"),
+        ("+#% Abklingzeitreduktion", "cooldown_reduction"),
+        ("+#% Ressourcengenerierung", "resource_generation"),
+        ("+# Maximale Ressource", "maximum_resource"),
+        ("+#% Schadensreduktion", "damage_reduction"),
+        ("+#% Schadensreduktion gegen nahe Gegner", "damage_reduction_from_close"),
+        ("+#% Feuerwiderstand", "fire_resistance"),
+        ("+#% Kältewiderstand", "cold_resistance"),
+        ("+#% Blitzwiderstand", "lightning_resistance"),
+        ("+#% Giftwiderstand", "poison_resistance"),
+        ("+#% Schattenwiderstand", "shadow_resistance"),
+        ("+#% Widerstand gegen alle Elemente", "all_resistance"),
+        ("+# Ränge aller Fertigkeiten", "all_skill_ranks"),
+        ("+# Ränge aller Defensivfertigkeiten", "defensive_skill_ranks"),
+        ("+#% Erhaltene Heilung", "healing_received"),
+    ],
+};
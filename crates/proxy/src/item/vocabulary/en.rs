@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "en",
+    decimal: '.',
+    spaced: true,
+    qualities: &[
+        ("sacred", Quality::Sacred),
+        ("ancestral", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mythic", Rarity::Mythic),
+        ("common", Rarity::Common),
+        ("magic", Rarity::Magic),
+        ("rare", Rarity::Rare),
+        ("legendary", Rarity::Legendary),
+        ("unique", Rarity::Unique),
+    ],
+    item_power: "Item Power",
+    armor: "Armor",
+    damage_per_second: "Damage Per Second",
+    damage_per_hit: "Damage per Hit",
+    attacks_per_second: "Attacks per Second",
+    empty_socket: "Empty Socket",
+    gems: &["Ruby", "Sapphire", "Emerald", "Diamond", "Topaz", "Amethyst", "Skull"],
+    imprinted: "Imprinted:",
+    ignored: &[
+        "Requires Level",
+        "Sell Value",
+        "Durability",
+        "Upgrades",
+        "Account Bound",
+        "Unique Equipment",
+        "Properties lost when equipped",
+        "Tempers",
+        "Masterwork",
+    ],
+    stats: &[
+        ("+# Strength", "strength"),
+        ("+# Dexterity", "dexterity"),
+        ("+# Intelligence", "intelligence"),
+        ("+# Willpower", "willpower"),
+        ("+# All Stats", "all_stats"),
+        ("+# Maximum Life", "maximum_life"),
+        ("+# Armor", "armor"),
+        ("+#% Total Armor", "total_armor"),
+        ("+#% Critical Strike Chance", "critical_strike_chance"),
+        ("+#% Critical Strike Damage", "critical_strike_damage"),
+        ("+#% Vulnerable Damage", "vulnerable_damage"),
+        ("+#% Attack Speed", "attack_speed"),
+        ("+#% Movement Speed", "movement_speed"),
+        ("+#% Cooldown Reduction", "cooldown_reduction"),
+        ("+#% Resource Generation", "resource_generation"),
+        ("+# Maximum Resource", "maximum_resource"),
+        ("+#% Damage Reduction", "damage_reduction"),
+        ("+#% Damage Reduction from Close Enemies", "damage_reduction_from_close"),
+        ("+#% Fire Resistance", "fire_resistance"),
+        ("+#% Cold Resistance", "cold_resistance"),
+        ("+#% Lightning Resistance", "lightning_resistance"),
+        ("+#% Poison Resistance", "poison_resistance"),
+        ("+#% Shadow Resistance", "shadow_resistance"),
+        ("+#% Resistance to All Elements", "all_resistance"),
+        ("+# Ranks to All Skills", "all_skill_ranks"),
+        ("+# Ranks of All Defensive Skills", "defensive_skill_ranks"),
+        ("+#% Healing Received", "healing_received"),
+    ],
+};
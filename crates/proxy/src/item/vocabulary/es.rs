@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "es",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("sagrad", Quality::Sacred),
+        ("ancestral", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mític", Rarity::Mythic),
+        ("común", Rarity::Common),
+        ("mágic", Rarity::Magic),
+        ("rar", Rarity::Rare),
+        ("legendari", Rarity::Legendary),
+        ("únic", Rarity::Unique),
+    ],
+    item_power: "Poder de objeto",
+    armor: "Armadura",
+    damage_per_second: "Daño por segundo",
+    damage_per_hit: "Daño por golpe",
+    attacks_per_second: "Ataques por segundo",
+    empty_socket: "Engaste vacío",
+    gems: &["Rubí", "Zafiro", "Esmeralda", "Diamante", "Topacio", "Amatista", "Calavera"],
+    imprinted: "Impreso:",
+    ignored: &[
+        "Requiere nivel",
+        "Valor de venta",
+        "Durabilidad",
+        "Mejoras",
+        "Vinculado a la cuenta",
+        "Equipo único",
+        "Propiedades que se pierden al equiparlo",
+        "Templados",
+        "Obra maestra",
+    ],
+    stats: &[
+        ("+# de fuerza", "strength"),
+        ("+# de destreza", "dexterity"),
+        ("+# de inteligencia", "intelligence"),
+        ("+# de voluntad", "willpower"),
+        ("+# a todos los atributos", "all_stats"),
+        ("+# de vida máxima", "maximum_life"),
+        ("+# de armadura", "armor"),
+        ("+#% de armadura total", "total_armor"),
+        ("+#% de probabilidad de golpe crítico", "critical_strike_chance"),
+        ("+#% de daño de golpe crítico", "critical_strike_damage"),
+        ("+#% de daño vulnerable", "vulnerable_damage"),
+        ("+#% de velocidad de ataque", "attack_speed"),
+        ("+#% de velocidad de movimiento", "movement_speed"),
+        ("+#% de reducción de tiempo de reutilización", "cooldown_reduction"),
+        ("+#% de generación de recursos", "resource_generation"),
+        ("+# de recurso máximo", "maximum_resource"),
+        ("+#% de reducción de daño", "damage_reduction"),
+        ("+#% de reducción de daño de enemigos cercanos", "damage_reduction_from_close"),
+        ("+#% de resistencia al fuego", "fire_resistance"),
+        ("+#% de resistencia al frío", "cold_resistance"),
+        ("+#% de resistencia al rayo", "lightning_resistance"),
+        ("+#% de resistencia al veneno", "poison_resistance"),
+        ("+#% de resistencia a las sombras", "shadow_resistance"),
+        ("+#% de resistencia a todos los elementos", "all_resistance"),
+        ("+# rangos de todas las habilidades", "all_skill_ranks"),
+        ("+# rangos de todas las habilidades defensivas", "defensive_skill_ranks"),
+        ("+#% de curación recibida", "healing_received"),
+    ],
+};
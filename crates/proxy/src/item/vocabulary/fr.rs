@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "fr",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("sacré", Quality::Sacred),
+        ("ancestra", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mythique", Rarity::Mythic),
+        ("commun", Rarity::Common),
+        ("magique", Rarity::Magic),
+        ("rare", Rarity::Rare),
+        ("légendaire", Rarity::Legendary),
+        ("unique", Rarity::Unique),
+    ],
+    item_power: "Puissance de l'objet",
+    armor: "Armure",
+    damage_per_second: "Dégâts par seconde",
+    damage_per_hit: "Dégâts par coup",
+    attacks_per_second: "Attaques par seconde",
+    empty_socket: "Châsse vide",
+    gems: &["Rubis", "Saphir", "Émeraude", "Diamant", "Topaze", "Améthyste", "Crâne"],
+    imprinted: "Empreinte :",
+    ignored: &[
+        "Niveau requis",
+        "Valeur de vente",
+        "Durabilité",
+        "Améliorations",
+        "Lié au compte",
+        "Équipement unique",
+        "Propriétés perdues une fois équipé",
+        "Trempes",
+        "Chef-d'œuvre",
+    ],
+    stats: &[
+        ("+# Force", "strength"),
+        ("+# Dextérité", "dexterity"),
+        ("+# Intelligence", "intelligence"),
+        ("+# Volonté", "willpower"),
+        ("+# à toutes les caractéristiques", "all_stats"),
+        ("+# Vie maximum", "maximum_life"),
+        ("+# Armure", "armor"),
+        ("+#% Armure totale", "total_armor"),
+        ("+#% Chances de coup critique", "critical_strike_chance"),
+        ("+#% Dégâts des coups critiques", "critical_strike_damage"),
+        ("+#% Dégâts contre les ennemis vulnérables", "vulnerable_damage"),
+        ("+#% Vitesse d'attaque", "attack_speed"),
+        ("+#% Vitesse de déplacement", "movement_speed"),
+        ("+#% Réduction du temps de recharge", "cooldown_reduction"),
+        ("+#% Génération de ressource", "resource_generation"),
+        ("+# Ressource maximum", "maximum_resource"),
+        ("+#% Réduction des dégâts", "damage_reduction"),
+        ("+#% Réduction des dégâts infligés par les ennemis proches", "damage_reduction_from_close"),
+        ("+#% Résistance au feu", "fire_resistance"),
+        ("+#% Résistance au froid", "cold_resistance"),
+        ("+#% Résistance à la foudre", "lightning_resistance"),
+        ("+#% Résistance au poison", "poison_resistance"),
+        ("+#% Résistance à l'ombre", "shadow_resistance"),
+        ("+#% Résistance à tous les éléments", "all_resistance"),
+        ("+# rangs de toutes les compétences", "all_skill_ranks"),
+        ("+# rangs de toutes les compétences défensives", "defensive_skill_ranks"),
+        ("+#% Soins reçus", "healing_received"),
+    ],
+};
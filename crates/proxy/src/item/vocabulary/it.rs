@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "it",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("sacr", Quality::Sacred),
+        ("ancestral", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mitic", Rarity::Mythic),
+        ("comun", Rarity::Common),
+        ("magic", Rarity::Magic),
+        ("rar", Rarity::Rare),
+        ("leggendari", Rarity::Legendary),
+        ("unic", Rarity::Unique),
+    ],
+    item_power: "Potere oggetto",
+    armor: "Armatura",
+    damage_per_second: "Danni al secondo",
+    damage_per_hit: "Danni per colpo",
+    attacks_per_second: "Attacchi al secondo",
+    empty_socket: "Incavo vuoto",
+    gems: &["Rubino", "Zaffiro", "Smeraldo", "Diamante", "Topazio", "Ametista", "Teschio"],
+    imprinted: "Impresso:",
+    ignored: &[
+        "Livello richiesto",
+        "Valore di vendita",
+        "Durabilità",
+        "Potenziamenti",
+        "Vincolato all'account",
+        "Equipaggiamento unico",
+        "Proprietà perse se equipaggiato",
+        "Tempre",
+        "Capolavoro",
+    ],
+    stats: &[
+        ("+# Forza", "strength"),
+        ("+# Destrezza", "dexterity"),
+        ("+# Intelligenza", "intelligence"),
+        ("+# Volontà", "willpower"),
+        ("+# a tutte le statistiche", "all_stats"),
+        ("+# Vita massima", "maximum_life"),
+        ("+# Armatura", "armor"),
+        ("+#% Armatura totale", "total_armor"),
+        ("+#% Probabilità di critico", "critical_strike_chance"),
+        ("+#% Danni da critico", "critical_strike_damage"),
+        ("+#% Danni da vulnerabilità", "vulnerable_damage"),
+        ("+#% Velocità d'attacco", "attack_speed"),
+        ("+#% Velocità di movimento", "movement_speed"),
+        ("+#% Riduzione della ricarica", "cooldown_reduction"),
+        ("+#% Generazione di risorse", "resource_generation"),
+        ("+# Risorsa massima", "maximum_resource"),
+        ("+#% Riduzione danni", "damage_reduction"),
+        ("+#% Riduzione danni dai nemici vicini", "damage_reduction_from_close"),
+        ("+#% Resistenza al fuoco", "fire_resistance"),
+        ("+#% Resistenza al freddo", "cold_resistance"),
+        ("+#% Resistenza al fulmine", "lightning_resistance"),
+        ("+#% Resistenza al veleno", "poison_resistance"),
+        ("+#% Resistenza all'ombra", "shadow_resistance"),
+        ("+#% Resistenza a tutti gli elementi", "all_resistance"),
+        ("+# gradi a tutte le abilità", "all_skill_ranks"),
+        ("+# gradi a tutte le abilità difensive", "defensive_skill_ranks"),
+        ("+#% Guarigione ricevuta", "healing_received"),
+    ],
+};
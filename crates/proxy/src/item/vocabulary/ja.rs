@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "ja",
+    decimal: '.',
+    spaced: false,
+    qualities: &[
+        ("神聖な", Quality::Sacred),
+        ("先祖の", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("ミシック・ユニーク", Rarity::Mythic),
+        ("ノーマル", Rarity::Common),
+        ("マジック", Rarity::Magic),
+        ("レア", Rarity::Rare),
+        ("レジェンダリー", Rarity::Legendary),
+        ("ユニーク", Rarity::Unique),
+    ],
+    item_power: "アイテムパワー",
+    armor: "アーマー",
+    damage_per_second: "秒間ダメージ",
+    damage_per_hit: "ヒットあたりのダメージ",
+    attacks_per_second: "秒間攻撃回数",
+    empty_socket: "空のソケット",
+    gems: &["ルビー", "サファイア", "エメラルド", "ダイヤモンド", "トパーズ", "アメジスト", "スカル"],
+    imprinted: "刻印:",
+    ignored: &[
+        "必要レベル",
+        "売却価格",
+        "耐久度",
+        "アップグレード",
+        "アカウント共有",
+        "ユニーク装備",
+        "装備すると失われる特性",
+        "焼き戻し",
+        "マスターワーク",
+    ],
+    stats: &[
+        ("+# 筋力", "strength"),
+        ("+# 器用さ", "dexterity"),
+        ("+# 知力", "intelligence"),
+        ("+# 意志力", "willpower"),
+        ("+# 全能力値", "all_stats"),
+        ("+# 最大ライフ", "maximum_life"),
+        ("+# アーマー", "armor"),
+        ("+#% 合計アーマー", "total_armor"),
+        ("+#% クリティカル率", "critical_strike_chance"),
+        ("+#% クリティカルダメージ", "critical_strike_damage"),
+        ("+#% 脆弱ダメージ", "vulnerable_damage"),
+        ("+#% 攻撃速度", "attack_speed"),
+        ("+#% 移動速度", "movement_speed"),
+        ("+#% クールダウン短縮", "cooldown_reduction"),
+        ("+#% リソース生成", "resource_generation"),
+        ("+# 最大リソース", "maximum_resource"),
+        ("+#% ダメージ軽減", "damage_reduction"),
+        ("+#% 近くの敵から受けるダメージ軽減", "damage_reduction_from_close"),
+        ("+#% 火炎耐性", "fire_resistance"),
+        ("+#% 冷気耐性", "cold_resistance"),
+        ("+#% 雷耐性", "lightning_resistance"),
+        ("+#% 毒耐性", "poison_resistance"),
+        ("+#% 暗黒耐性", "shadow_resistance"),
+        ("+#% 全元素耐性", "all_resistance"),
+        ("+# 全スキルランク", "all_skill_ranks"),
+        ("+# 全防御スキルランク", "defensive_skill_ranks"),
+        ("+#% 受ける回復量", "healing_received"),
+    ],
+};
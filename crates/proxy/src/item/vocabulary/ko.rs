@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "ko",
+    decimal: '.',
+    spaced: true,
+    qualities: &[
+        ("신성", Quality::Sacred),
+        ("선조", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("신화", Rarity::Mythic),
+        ("일반", Rarity::Common),
+        ("마법", Rarity::Magic),
+        ("희귀", Rarity::Rare),
+        ("전설", Rarity::Legendary),
+        ("고유", Rarity::Unique),
+    ],
+    item_power: "아이템 위력",
+    armor: "방어도",
+    damage_per_second: "초당 피해",
+    damage_per_hit: "타격당 피해",
+    attacks_per_second: "초당 공격 횟수",
+    empty_socket: "빈 홈",
+    gems: &["루비", "사파이어", "에메랄드", "다이아몬드", "토파즈", "자수정", "해골"],
+    imprinted: "각인:",
+    ignored: &[
+        "요구 레벨",
+        "판매 가격",
+        "내구도",
+        "강화",
+        "계정 귀속",
+        "고유 장비",
+        "장착 시 사라지는 속성",
+        "담금질",
+        "걸작",
+    ],
+    stats: &[
+        ("+# 힘", "strength"),
+        ("+# 민첩", "dexterity"),
+        ("+# 지능", "intelligence"),
+        ("+# 의지력", "willpower"),
+        ("+# 모든 능력치", "all_stats"),
+        ("+# 최대 생명력", "maximum_life"),
+        ("+# 방어도", "armor"),
+        ("+#% 총 방어도", "total_armor"),
+        ("+#% 극대화 확률", "critical_strike_chance"),
+        ("+#% 극대화 피해", "critical_strike_damage"),
+        ("+#% 취약 피해", "vulnerable_damage"),
+        ("+#% 공격 속도", "attack_speed"),
+        ("+#% 이동 속도", "movement_speed"),
+        ("+#% 재사용 대기시간 감소", "cooldown_reduction"),
+        ("+#% 자원 생성", "resource_generation"),
+        ("+# 최대 자원", "maximum_resource"),
+        ("+#% 피해 감소", "damage_reduction"),
+        ("+#% 근거리 적에게 받는 피해 감소", "damage_reduction_from_close"),
+        ("+#% 화염 저항", "fire_resistance"),
+        ("+#% 냉기 저항", "cold_resistance"),
+        ("+#% 번개 저항", "lightning_resistance"),
+        ("+#% 독 저항", "poison_resistance"),
+        ("+#% 암흑 저항", "shadow_resistance"),
+        ("+#% 모든 원소 저항", "all_resistance"),
+        ("+# 모든 기술 등급", "all_skill_ranks"),
+        ("+# 모든 방어 기술 등급", "defensive_skill_ranks"),
+        ("+#% 받는 치유량", "healing_received"),
+    ],
+};
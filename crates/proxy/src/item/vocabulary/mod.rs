@@ -0,0 +1,73 @@
+use super::Quality;
+use super::Rarity;
+
+mod de;
+mod en;
+mod es;
+mod es_mx;
+mod fr;
+mod it;
+mod ja;
+mod ko;
+mod pl;
+mod pt;
+mod ru;
+mod tr;
+mod zh_cn;
+mod zh_tw;
+
+/// Every language the tooltip can be in, the first one wins ties.
+pub static LOCALES: &[&Vocabulary] = &[
+    &en::VOCABULARY,
+    &de::VOCABULARY,
+    &fr::VOCABULARY,
+    &es::VOCABULARY,
+    &es_mx::VOCABULARY,
+    &it::VOCABULARY,
+    &pt::VOCABULARY,
+    &pl::VOCABULARY,
+    &ru::VOCABULARY,
+    &tr::VOCABULARY,
+    &ko::VOCABULARY,
+    &ja::VOCABULARY,
+    &zh_cn::VOCABULARY,
+    &zh_tw::VOCABULARY,
+];
+
+/// Words of the item tooltip in one language.
+///
+/// Quality and rarity words are lowercase stems that match words with up to
+/// [`MAX_ENDING`] more letters, so `legendär` matches `Uralter legendärer
+/// Helm`. They can be anywhere in the line and the remaining words are the
+/// item type. In languages written without spaces they are the words as
+/// they appear, like `先祖` of `先祖传奇头盔`.
+pub struct Vocabulary {
+    /// Language code like `en` or `zh-CN`.
+    pub locale: &'static str,
+    /// Decimal separator, the other one of `.` and `,` separates thousands.
+    pub decimal: char,
+    /// Whether words are separated by spaces.
+    pub spaced: bool,
+    pub qualities: &'static [(&'static str, Quality)],
+    /// Rarity words, `Mythic` first since the line also says `Unique`.
+    pub rarities: &'static [(&'static str, Rarity)],
+    /// Label of the item power next to a value like `925` or `800+25`.
+    pub item_power: &'static str,
+    pub armor: &'static str,
+    pub damage_per_second: &'static str,
+    pub damage_per_hit: &'static str,
+    pub attacks_per_second: &'static str,
+    pub empty_socket: &'static str,
+    /// Gem words, socketed gems are followed by their effect.
+    pub gems: &'static [&'static str],
+    /// Prefix of the name of an imprinted aspect.
+    pub imprinted: &'static str,
+    /// Prefixes of lines without anything to parse.
+    pub ignored: &'static [&'static str],
+    /// Affix stats like `+#% Critical Strike Chance` by their id, compared
+    /// in lowercase without spaces before `%`.
+    pub stats: &'static [(&'static str, &'static str)],
+}
+
+/// Letters of an inflection after a stem.
+pub const MAX_ENDING: usize = 3;
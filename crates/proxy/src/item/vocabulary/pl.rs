@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "pl",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("uświęcon", Quality::Sacred),
+        ("prastar", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mityczn", Rarity::Mythic),
+        ("zwykł", Rarity::Common),
+        ("magiczn", Rarity::Magic),
+        ("rzadk", Rarity::Rare),
+        ("legendarn", Rarity::Legendary),
+        ("unikatow", Rarity::Unique),
+    ],
+    item_power: "Moc przedmiotu",
+    armor: "Pancerz",
+    damage_per_second: "Obrażenia na sekundę",
+    damage_per_hit: "Obrażenia na trafienie",
+    attacks_per_second: "Ataki na sekundę",
+    empty_socket: "Pusty otwór",
+    gems: &["Rubin", "Szafir", "Szmaragd", "Diament", "Topaz", "Ametyst", "Czaszka"],
+    imprinted: "Nadrukowano:",
+    ignored: &[
+        "Wymagany poziom",
+        "Wartość sprzedaży",
+        "Wytrzymałość",
+        "Ulepszenia",
+        "Przypisane do konta",
+        "Unikatowy ekwipunek",
+        "Właściwości utracone po założeniu",
+        "Hartowania",
+        "Arcydzieło",
+    ],
+    stats: &[
+        ("+# siły", "strength"),
+        ("+# zręczności", "dexterity"),
+        ("+# inteligencji", "intelligence"),
+        ("+# siły woli", "willpower"),
+        ("+# do wszystkich atrybutów", "all_stats"),
+        ("+# maksymalnego zdrowia", "maximum_life"),
+        ("+# pancerza", "armor"),
+        ("+#% całkowitego pancerza", "total_armor"),
+        ("+#% szansy na trafienie krytyczne", "critical_strike_chance"),
+        ("+#% obrażeń od trafień krytycznych", "critical_strike_damage"),
+        ("+#% obrażeń zadawanych odsłoniętym", "vulnerable_damage"),
+        ("+#% szybkości ataku", "attack_speed"),
+        ("+#% szybkości ruchu", "movement_speed"),
+        ("+#% skrócenia czasu odnowienia", "cooldown_reduction"),
+        ("+#% generowania zasobu", "resource_generation"),
+        ("+# maksymalnego zasobu", "maximum_resource"),
+        ("+#% redukcji obrażeń", "damage_reduction"),
+        ("+#% redukcji obrażeń od pobliskich wrogów", "damage_reduction_from_close"),
+        ("+#% odporności na ogień", "fire_resistance"),
+        ("+#% odporności na zimno", "cold_resistance"),
+        ("+#% odporności na błyskawice", "lightning_resistance"),
+        ("+#% odporności na truciznę", "poison_resistance"),
+        ("+#% odporności na cień", "shadow_resistance"),
+        ("+#% odporności na wszystkie żywioły", "all_resistance"),
+        ("+# do poziomu wszystkich umiejętności", "all_skill_ranks"),
+        ("+# do poziomu wszystkich umiejętności obronnych", "defensive_skill_ranks"),
+        ("+#% otrzymywanego leczenia", "healing_received"),
+    ],
+};
@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "pt",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("sagrad", Quality::Sacred),
+        ("ancestra", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mític", Rarity::Mythic),
+        ("comum", Rarity::Common),
+        ("mágic", Rarity::Magic),
+        ("rar", Rarity::Rare),
+        ("lendári", Rarity::Legendary),
+        ("únic", Rarity::Unique),
+    ],
+    item_power: "Poder do Item",
+    armor: "Armadura",
+    damage_per_second: "Dano por Segundo",
+    damage_per_hit: "Dano por Golpe",
+    attacks_per_second: "Ataques por Segundo",
+    empty_socket: "Engaste Vazio",
+    gems: &["Rubi", "Safira", "Esmeralda", "Diamante", "Topázio", "Ametista", "Crânio"],
+    imprinted: "Impresso:",
+    ignored: &[
+        "Requer nível",
+        "Valor de venda",
+        "Durabilidade",
+        "Aprimoramentos",
+        "Vinculado à conta",
+        "Equipamento Único",
+        "Propriedades perdidas ao equipar",
+        "Têmperas",
+        "Obra-prima",
+    ],
+    stats: &[
+        ("+# Força", "strength"),
+        ("+# Destreza", "dexterity"),
+        ("+# Inteligência", "intelligence"),
+        ("+# Força de Vontade", "willpower"),
+        ("+# a Todos os Atributos", "all_stats"),
+        ("+# Vida Máxima", "maximum_life"),
+        ("+# Armadura", "armor"),
+        ("+#% Armadura Total", "total_armor"),
+        ("+#% Chance de Golpe Crítico", "critical_strike_chance"),
+        ("+#% Dano de Golpe Crítico", "critical_strike_damage"),
+        ("+#% Dano Vulnerável", "vulnerable_damage"),
+        ("+#% Velocidade de Ataque", "attack_speed"),
+        ("+#% Velocidade de Movimento", "movement_speed"),
+        ("+#% Redução de Recarga", "cooldown_reduction"),
+        ("+#% Geração de Recurso", "resource_generation"),
+        ("+# Recurso Máximo", "maximum_resource"),
+        ("+#% Redução de Dano", "damage_reduction"),
+        ("+#% Redução de Dano de Inimigos Próximos", "damage_reduction_from_close"),
+        ("+#% Resistência a Fogo", "fire_resistance"),
+        ("+#% Resistência a Frio", "cold_resistance"),
+        ("+#% Resistência a Raio", "lightning_resistance"),
+        ("+#% Resistência a Veneno", "poison_resistance"),
+        ("+#% Resistência a Sombra", "shadow_resistance"),
+        ("+#% Resistência a Todos os Elementos", "all_resistance"),
+        ("+# Graus em Todas as Habilidades", "all_skill_ranks"),
+        ("+# Graus em Todas as Habilidades Defensivas", "defensive_skill_ranks"),
+        ("+#% Cura Recebida", "healing_received"),
+    ],
+};
@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "ru",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("священн", Quality::Sacred),
+        ("древн", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("мифическ", Rarity::Mythic),
+        ("обычн", Rarity::Common),
+        ("волшебн", Rarity::Magic),
+        ("редк", Rarity::Rare),
+        ("легендарн", Rarity::Legendary),
+        ("уникальн", Rarity::Unique),
+    ],
+    item_power: "Сила предмета",
+    armor: "Броня",
+    damage_per_second: "Урон в секунду",
+    damage_per_hit: "Урон за удар",
+    attacks_per_second: "Атак в секунду",
+    empty_socket: "Пустое гнездо",
+    gems: &["Рубин", "Сапфир", "Изумруд", "Алмаз", "Топаз", "Аметист", "Череп"],
+    imprinted: "Запечатлено:",
+    ignored: &[
+        "Требуется уровень",
+        "Цена продажи",
+        "Прочность",
+        "Улучшения",
+        "Привязано к учетной записи",
+        "Уникальное снаряжение",
+        "Свойства, которые будут утрачены",
+        "Закалки",
+        "Шедевр",
+    ],
+    stats: &[
+        ("+# к силе", "strength"),
+        ("+# к ловкости", "dexterity"),
+        ("+# к интеллекту", "intelligence"),
+        ("+# к силе воли", "willpower"),
+        ("+# ко всем характеристикам", "all_stats"),
+        ("+# к максимальному запасу здоровья", "maximum_life"),
+        ("+# к броне", "armor"),
+        ("+#% к общей броне", "total_armor"),
+        ("+#% к вероятности критического удара", "critical_strike_chance"),
+        ("+#% к урону от критического удара", "critical_strike_damage"),
+        ("+#% к урону по уязвимым целям", "vulnerable_damage"),
+        ("+#% к скорости атаки", "attack_speed"),
+        ("+#% к скорости передвижения", "movement_speed"),
+        ("+#% к сокращению времени восстановления", "cooldown_reduction"),
+        ("+#% к выработке ресурса", "resource_generation"),
+        ("+# к максимальному запасу ресурса", "maximum_resource"),
+        ("+#% к снижению урона", "damage_reduction"),
+        ("+#% к снижению урона от ближайших врагов", "damage_reduction_from_close"),
+        ("+#% к сопротивлению огню", "fire_resistance"),
+        ("+#% к сопротивлению холоду", "cold_resistance"),
+        ("+#% к сопротивлению молнии", "lightning_resistance"),
+        ("+#% к сопротивлению яду", "poison_resistance"),
+        ("+#% к сопротивлению темной магии", "shadow_resistance"),
+        ("+#% к сопротивлению всем стихиям", "all_resistance"),
+        ("+# к уровню всех умений", "all_skill_ranks"),
+        ("+# к уровню всех защитных умений", "defensive_skill_ranks"),
+        ("+#% к получаемому исцелению", "healing_received"),
+    ],
+};
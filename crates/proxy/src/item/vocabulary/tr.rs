@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "tr",
+    decimal: ',',
+    spaced: true,
+    qualities: &[
+        ("kutsal", Quality::Sacred),
+        ("kadim", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("mitik", Rarity::Mythic),
+        ("sıradan", Rarity::Common),
+        ("büyülü", Rarity::Magic),
+        ("nadir", Rarity::Rare),
+        ("efsanevi", Rarity::Legendary),
+        ("eşsiz", Rarity::Unique),
+    ],
+    item_power: "Eşya Gücü",
+    armor: "Zırh",
+    damage_per_second: "Saniye Başına Hasar",
+    damage_per_hit: "Vuruş Başına Hasar",
+    attacks_per_second: "Saniye Başına Saldırı",
+    empty_socket: "Boş Yuva",
+    gems: &["Yakut", "Safir", "Zümrüt", "Elmas", "Topaz", "Ametist", "Kafatası"],
+    imprinted: "İşlenmiş:",
+    ignored: &[
+        "Gereken Seviye",
+        "Satış Değeri",
+        "Dayanıklılık",
+        "Geliştirmeler",
+        "Hesaba Bağlı",
+        "Eşsiz Ekipman",
+        "Kuşanınca Kaybolacak Özellikler",
+        "Temperlemeler",
+        "Ustalık İşi",
+    ],
+    stats: &[
+        ("+# Güç", "strength"),
+        ("+# Çeviklik", "dexterity"),
+        ("+# Zekâ", "intelligence"),
+        ("+# İrade", "willpower"),
+        ("+# Tüm Nitelikler", "all_stats"),
+        ("+# Azami Can", "maximum_life"),
+        ("+# Zırh", "armor"),
+        ("+#% Toplam Zırh", "total_armor"),
+        ("+#% Kritik Vuruş Şansı", "critical_strike_chance"),
+        ("+#% Kritik Vuruş Hasarı", "critical_strike_damage"),
+        ("+#% Savunmasız Hasarı", "vulnerable_damage"),
+        ("+#% Saldırı Hızı", "attack_speed"),
+        ("+#% Hareket Hızı", "movement_speed"),
+        ("+#% Bekleme Süresi Azaltma", "cooldown_reduction"),
+        ("+#% Kaynak Üretimi", "resource_generation"),
+        ("+# Azami Kaynak", "maximum_resource"),
+        ("+#% Hasar Azaltma", "damage_reduction"),
+        ("+#% Yakındaki Düşmanlardan Alınan Hasarı Azaltma", "damage_reduction_from_close"),
+        ("+#% Ateş Direnci", "fire_resistance"),
+        ("+#% Soğuk Direnci", "cold_resistance"),
+        ("+#% Yıldırım Direnci", "lightning_resistance"),
+        ("+#% Zehir Direnci", "poison_resistance"),
+        ("+#% Gölge Direnci", "shadow_resistance"),
+        ("+#% Tüm Element Dirençleri", "all_resistance"),
+        ("+# Tüm Beceri Seviyeleri", "all_skill_ranks"),
+        ("+# Tüm Savunma Becerisi Seviyeleri", "defensive_skill_ranks"),
+        ("+#% Alınan İyileşme", "healing_received"),
+    ],
+};
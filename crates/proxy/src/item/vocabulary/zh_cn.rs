@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "zh-CN",
+    decimal: '.',
+    spaced: false,
+    qualities: &[
+        ("神圣", Quality::Sacred),
+        ("先祖", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("神话暗金", Rarity::Mythic),
+        ("普通", Rarity::Common),
+        ("魔法", Rarity::Magic),
+        ("稀有", Rarity::Rare),
+        ("传奇", Rarity::Legendary),
+        ("暗金", Rarity::Unique),
+    ],
+    item_power: "物品强度",
+    armor: "护甲值",
+    damage_per_second: "每秒伤害",
+    damage_per_hit: "每次攻击伤害",
+    attacks_per_second: "每秒攻击次数",
+    empty_socket: "空插槽",
+    gems: &["红宝石", "蓝宝石", "绿宝石", "钻石", "黄宝石", "紫宝石", "骷髅"],
+    imprinted: "铭刻：",
+    ignored: &[
+        "需要等级",
+        "售价",
+        "耐久度",
+        "升级",
+        "账号绑定",
+        "暗金装备",
+        "装备后将失去的属性",
+        "淬炼",
+        "精造",
+    ],
+    stats: &[
+        ("+# 力量", "strength"),
+        ("+# 敏捷", "dexterity"),
+        ("+# 智力", "intelligence"),
+        ("+# 意力", "willpower"),
+        ("+# 全属性", "all_stats"),
+        ("+# 最大生命", "maximum_life"),
+        ("+# 护甲", "armor"),
+        ("+#% 总护甲", "total_armor"),
+        ("+#% 暴击几率", "critical_strike_chance"),
+        ("+#% 暴击伤害", "critical_strike_damage"),
+        ("+#% 易伤伤害", "vulnerable_damage"),
+        ("+#% 攻击速度", "attack_speed"),
+        ("+#% 移动速度", "movement_speed"),
+        ("+#% 冷却时间缩减", "cooldown_reduction"),
+        ("+#% 资源生成", "resource_generation"),
+        ("+# 最大资源", "maximum_resource"),
+        ("+#% 伤害减免", "damage_reduction"),
+        ("+#% 近距离敌人伤害减免", "damage_reduction_from_close"),
+        ("+#% 火焰抗性", "fire_resistance"),
+        ("+#% 冰霜抗性", "cold_resistance"),
+        ("+#% 闪电抗性", "lightning_resistance"),
+        ("+#% 毒素抗性", "poison_resistance"),
+        ("+#% 暗影抗性", "shadow_resistance"),
+        ("+#% 全元素抗性", "all_resistance"),
+        ("+# 所有技能等级", "all_skill_ranks"),
+        ("+# 所有防御技能等级", "defensive_skill_ranks"),
+        ("+#% 受到的治疗效果", "healing_received"),
+    ],
+};
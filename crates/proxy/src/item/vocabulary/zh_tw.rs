@@ -0,0 +1,69 @@
+use super::Quality;
+use super::Rarity;
+use super::Vocabulary;
+
+pub static VOCABULARY: Vocabulary = Vocabulary {
+    locale: "zh-TW",
+    decimal: '.',
+    spaced: false,
+    qualities: &[
+        ("神聖", Quality::Sacred),
+        ("先祖", Quality::Ancestral),
+    ],
+    rarities: &[
+        ("神話獨特", Rarity::Mythic),
+        ("普通", Rarity::Common),
+        ("魔法", Rarity::Magic),
+        ("稀有", Rarity::Rare),
+        ("傳奇", Rarity::Legendary),
+        ("獨特", Rarity::Unique),
+    ],
+    item_power: "物品威能",
+    armor: "護甲值",
+    damage_per_second: "每秒傷害",
+    damage_per_hit: "每擊傷害",
+    attacks_per_second: "每秒攻擊次數",
+    empty_socket: "空的插槽",
+    gems: &["紅寶石", "藍寶石", "翡翠", "鑽石", "黃玉", "紫水晶", "骷髏"],
+    imprinted: "銘印：",
+    ignored: &[
+        "需要等級",
+        "售價",
+        "耐久度",
+        "升級",
+        "帳號綁定",
+        "獨特裝備",
+        "裝備後會失去的屬性",
+        "淬鍊",
+        "大師工藝",
+    ],
+    stats: &[
+        ("+# 力量", "strength"),
+        ("+# 敏捷", "dexterity"),
+        ("+# 智力", "intelligence"),
+        ("+# 意志力", "willpower"),
+        ("+# 所有屬性", "all_stats"),
+        ("+# 最大生命", "maximum_life"),
+        ("+# 護甲", "armor"),
+        ("+#% 總護甲", "total_armor"),
+        ("+#% 爆擊率", "critical_strike_chance"),
+        ("+#% 爆擊傷害", "critical_strike_damage"),
+        ("+#% 易傷傷害", "vulnerable_damage"),
+        ("+#% 攻擊速度", "attack_speed"),
+        ("+#% 移動速度", "movement_speed"),
+        ("+#% 冷卻時間縮短", "cooldown_reduction"),
+        ("+#% 資源產生", "resource_generation"),
+        ("+# 最大資源", "maximum_resource"),
+        ("+#% 傷害減免", "damage_reduction"),
+        ("+#% 近距離敵人傷害減免", "damage_reduction_from_close"),
+        ("+#% 火焰抗性", "fire_resistance"),
+        ("+#% 冰霜抗性", "cold_resistance"),
+        ("+#% 閃電抗性", "lightning_resistance"),
+        ("+#% 毒素抗性", "poison_resistance"),
+        ("+#% 暗影抗性", "shadow_resistance"),
+        ("+#% 所有元素抗性", "all_resistance"),
+        ("+# 所有技能等級", "all_skill_ranks"),
+        ("+# 所有防禦技能等級", "defensive_skill_ranks"),
+        ("+#% 受到的治療量", "healing_received"),
+    ],
+};
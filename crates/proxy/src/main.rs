@@ -1,21 +1,68 @@
-use std::sync::atomic::AtomicBool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc;
 use std::sync::mpsc::Sender;
 use std::sync::mpsc::Receiver;
 use std::io;
 use std::io::Read;
-use std::net::SocketAddrV4;
-use std::net::Ipv4Addr;
+use std::net::SocketAddr;
 use std::net::TcpListener;
+use std::net::TcpStream;
+use std::path::Path;
 use std::thread;
+use std::time::Duration;
 
+use tts_air_ipc::Transport;
+use tts_air_ipc::client::ConnectionState;
+use tts_air_ipc::client::Controller;
+use tts_air_ipc::client::TtsClient;
+use tts_air_ipc::client::TtsEvent;
+use tts_air_ipc::client::TtsEventKind;
+use tts_air_ipc::protocol::Backend;
+
+mod config;
+use config::Config;
+use config::ConfigError;
+mod filter;
+use filter::Filters;
+mod item;
+mod methods;
+use methods::Session;
+use methods::Shared;
+mod origin;
+use origin::OriginPattern;
+mod rpc;
 mod stream;
 use stream::NonblockingStream;
 mod tts;
 use tts::TtsAir;
 
-const LISTEN_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 61806);
+const NAME: &str = concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION"));
+/// Messages replayed on the first connection to pick up the current tooltip.
+const REPLAY_LAST: u64 = 1;
+/// How often to look for new capture instances.
+const DISCOVER_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// Connect attempts before an instance is assumed to be gone.
+const RETRY_LIMIT: u32 = 5;
+/// How long a WebSocket request waits for a capture instance to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Connected capture instances by process id and backend.
+type Controllers = Arc<Mutex<HashMap<(u32, Backend), Instance>>>;
+
+/// Connected capture instance.
+#[derive(Clone)]
+struct Instance {
+    controller: Controller,
+    replay: Arc<methods::ReplayRoute>,
+}
+
+/// Event of the capture instance with the given process id, with the
+/// connection it is for if it was replayed on request.
+type Captured = (u32, TtsEvent, Option<u64>);
 
 fn main() {
     println!("{}@{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
@@ -36,37 +83,74 @@ fn main() {
 
     if cfg!(debug_assertions) {
         builder.filter_module("tts_air_proxy", log::LevelFilter::Trace);
+        builder.filter_module("tts_air_ipc", log::LevelFilter::Trace);
     } else if std::env::var_os("RUST_LOG").is_none() {
         builder.filter_module("tts_air_proxy", log::LevelFilter::Info);
+        builder.filter_module("tts_air_ipc", log::LevelFilter::Info);
     }
 
     let _ = builder.try_init();
 
+    let mut config = match load_config() {
+        Ok(config) => config,
+        Err(e) => exit_with_error(&e.to_string()),
+    };
+
     let mut args = std::env::args();
     let _ = args.next();
 
     let mut do_default = true;
+    let mut do_proxy = false;
     let mut tts = None;
-    let mut proxy = None;
-    for arg in args {
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "--proxy" => {
                 do_default = false;
-                proxy = Some(thread::spawn(start_proxy));
+                do_proxy = true;
             }
             "--test" => {
                 do_default = false;
                 tts = Some(TtsAir::new());
             }
+            "--listen" => {
+                let res = match args.next() {
+                    Some(list) => config.set_listen("--listen", &list),
+                    None => Err(ConfigError::Invalid("--listen", "missing address".to_string())),
+                };
+                if let Err(e) = res {
+                    exit_with_error(&e.to_string());
+                }
+            }
+            "--allow-origin" => {
+                let res = match args.next() {
+                    Some(pattern) => config.allow_origin("--allow-origin", &pattern),
+                    None => Err(ConfigError::Invalid("--allow-origin", "missing origin".to_string())),
+                };
+                if let Err(e) = res {
+                    exit_with_error(&e.to_string());
+                }
+            }
+            "--record" => {
+                let Some(dir) = args.next() else {
+                    exit_with_error(&ConfigError::Invalid("--record", "missing directory".to_string()).to_string());
+                };
+                config.record = Some(dir.into());
+            }
             _ => (),
         }
     }
 
-    if do_default {
-        if cfg!(debug_assertions) {
+    let mut proxy = None;
+    if do_proxy || (do_default && !cfg!(debug_assertions)) {
+        let listeners = match bind(&config.listen) {
+            Ok(listeners) => listeners,
+            Err(e) => exit_with_error(&e),
+        };
 
+        if do_proxy {
+            proxy = Some(thread::spawn(move || start_proxy(listeners, config)));
         } else {
-            start_proxy();
+            start_proxy(listeners, config);
         }
     }
 
@@ -92,6 +176,16 @@ fn main() {
                             tts = Some(TtsAir::new());
                         }
                     }
+                    "/stop" => {
+                        if let Some(tts) = &mut tts {
+                            tts.stop();
+                        }
+                    }
+                    text if text.starts_with("/braille ") => {
+                        if let Some(tts) = &mut tts {
+                            tts.braille(&text["/braille ".len()..]);
+                        }
+                    }
                     text => {
                         if let Some(tts) = &mut tts {
                             tts.say(text);
@@ -105,7 +199,79 @@ fn main() {
     }
 }
 
+fn exit_with_error(e: &str) -> ! {
+    log::error!("{e}");
+    eprintln!("error: {e}");
+    std::process::exit(1);
+}
+
+/// Read the config file next to the executable and apply [`config::LISTEN_ENV`].
+fn load_config() -> Result<Config, ConfigError> {
+    let mut config = match std::env::current_exe()
+        .ok()
+        .and_then(|path| Some(path.parent()?.to_path_buf()))
+    {
+        Some(dir) => Config::load(&dir)?,
+        None => Config::default(),
+    };
+
+    if let Ok(list) = std::env::var(config::LISTEN_ENV) {
+        config.set_listen(config::LISTEN_ENV, &list)?;
+    }
+    Ok(config)
+}
+
+/// Listen on every address in `addrs`, explaining why an address is taken.
+fn bind(addrs: &[SocketAddr]) -> Result<Vec<TcpListener>, String> {
+    let mut listeners = Vec::new();
+    for addr in addrs {
+        match TcpListener::bind(addr) {
+            Ok(listener) => {
+                if !addr.ip().is_loopback() {
+                    log::warn!("listening on {addr} which is reachable from other computers");
+                }
+                log::info!("listening on {addr}");
+                listeners.push(listener);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+                return Err(match probe(*addr) {
+                    Some(version) => format!("{addr} is already in use by tts-air-proxy {version}, \
+                        close it or choose another address with --listen"),
+                    None => format!("{addr} is already in use by another program, \
+                        choose another address with --listen"),
+                });
+            }
+            Err(e) => return Err(format!("failed to listen on {addr}: {e}")),
+        }
+    }
+    Ok(listeners)
+}
+
+/// Version of the `tts-air-proxy` answering at `addr`, if any.
+fn probe(addr: SocketAddr) -> Option<String> {
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    let stream = TcpStream::connect_timeout(&addr, TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(TIMEOUT)).ok()?;
+    let mut request = tungstenite::client::IntoClientRequest::into_client_request(format!("ws://{addr}/")).ok()?;
+    request.headers_mut().insert("origin", tungstenite::http::HeaderValue::from_static("null"));
+    let (mut ws, _) = tungstenite::client(request, stream).ok()?;
+
+    // the proxy greets every connection with `info`
+    let tungstenite::Message::Text(json) = ws.read_message().ok()? else {
+        return None;
+    };
+    let json = serde_json::from_str::<serde_json::Value>(&json).ok()?;
+    let version = json["args"]["proxy_version"].as_str().filter(|_| json["method"] == "info")?;
+    let _ = ws.close(None);
+    Some(version.to_string())
+}
+
 struct WebSocketContext {
+    /// Matches answers of requests to their connection.
+    id: u64,
+    session: Session,
     ws: tungstenite::WebSocket<NonblockingStream>,
     origin: String,
     pipe_notify_state: bool,
@@ -117,71 +283,239 @@ impl Drop for WebSocketContext {
     }
 }
 
-fn start_proxy() {
-    let pipe_connected: AtomicBool = AtomicBool::new(false);
-    let websocket_connected: AtomicBool = AtomicBool::new(false);
+/// `instance` is the process id of the capture library that sent `event` and
+/// `seq` is the sequence id of the event in the proxy history.
+fn event_json(seq: u64, instance: u32, event: &TtsEvent) -> serde_json::Value {
+    let backend = event.backend.name();
+    // milliseconds like `Date.now()`
+    let timestamp = event.timestamp / 1000;
+    match &event.kind {
+        TtsEventKind::Message(text) => serde_json::json!({
+            "method": "tts_message",
+            "args": {
+                "message": text,
+                "backend": backend,
+                "instance": instance,
+                "seq": seq,
+                "timestamp": timestamp,
+            }
+        }),
+        TtsEventKind::Braille(text) => serde_json::json!({
+            "method": "braille",
+            "args": {
+                "message": text,
+                "backend": backend,
+                "instance": instance,
+                "seq": seq,
+                "timestamp": timestamp,
+            }
+        }),
+        TtsEventKind::Stop => serde_json::json!({
+            "method": "stop",
+            "args": {
+                "backend": backend,
+                "instance": instance,
+                "seq": seq,
+                "timestamp": timestamp,
+            },
+        }),
+    }
+}
+
+/// `item` message sent after the `tts_message` of an item tooltip, with the
+/// same `seq`.
+fn item_json(seq: u64, instance: u32, event: &TtsEvent, item: &item::Item) -> serde_json::Value {
+    let mut args = serde_json::to_value(item).unwrap();
+    args["instance"] = instance.into();
+    args["seq"] = seq.into();
+    args["timestamp"] = (event.timestamp / 1000).into();
+    serde_json::json!({
+        "method": "item",
+        "args": args,
+    })
+}
+
+fn event_item(event: &TtsEvent) -> Option<item::Item> {
+    match &event.kind {
+        TtsEventKind::Message(text) => item::parse(text),
+        _ => None,
+    }
+}
+
+/// `filter_match` message sent after the `item` for each matching rule.
+fn filter_match_json(seq: u64, instance: u32, event: &TtsEvent, m: filter::Match) -> serde_json::Value {
+    serde_json::json!({
+        "method": "filter_match",
+        "args": {
+            "rule": m.rule,
+            "terms": m.terms,
+            "instance": instance,
+            "seq": seq,
+            "timestamp": event.timestamp / 1000,
+        },
+    })
+}
+
+/// Messages for `event` replayed by capture `instance`, as recorded when it
+/// was sent live. Events no longer kept get a `null` sequence id.
+fn replayed_json(shared: &Shared, instance: u32, event: &TtsEvent) -> Vec<(&'static str, String)> {
+    let mut messages = match shared.find(instance, event.backend, event.seq) {
+        Some(entry) => entry.messages().map(|(method, json)| (method, json.clone())).collect::<Vec<_>>(),
+        None => {
+            let mut json = event_json(0, instance, event);
+            json["args"]["seq"] = serde_json::Value::Null;
+            vec![(methods::event_method(&event.kind), json)]
+        }
+    };
+    messages.iter_mut()
+        .map(|(method, json)| {
+            json["replay"] = true.into();
+            (*method, json.to_string())
+        })
+        .collect()
+}
+
+fn start_proxy(listeners: Vec<TcpListener>, config: Config) {
+    let pipes_connected = Arc::new(AtomicUsize::new(0));
+    let controllers = Controllers::default();
 
     let (send, recv) = mpsc::channel();
     let (send_ws, recv_ws) = mpsc::channel();
-    let server = TcpListener::bind(LISTEN_ADDR).unwrap();
 
     thread::scope(|s| {
-        s.spawn(|| proxy_tts_listen(send, &pipe_connected, &websocket_connected));
-        s.spawn(|| proxy_ws_listen(server, send_ws));
-        s.spawn(|| proxy_ws_broadcast(recv, recv_ws, &pipe_connected, &websocket_connected));
+        for server in listeners {
+            let send_ws = send_ws.clone();
+            let origins = &config.origins;
+            s.spawn(move || proxy_ws_listen(server, send_ws, origins));
+        }
+        drop(send_ws);
+
+        s.spawn(|| proxy_tts_listen::<tts_air_ipc::Pipe>(send, pipes_connected.clone(), controllers.clone()));
+        let filters = Filters::new(config.filters.clone());
+        s.spawn(|| {
+            let record = config.record.as_deref();
+            proxy_ws_broadcast(recv, recv_ws, &pipes_connected, &controllers, config.history, filters, record)
+        });
     });
 }
 
-fn proxy_tts_listen(
-    send: Sender<String>,
-    pipe_connected: &AtomicBool,
-    websocket_connected: &AtomicBool,
+/// Connect to every capture instance, including ones started later.
+fn proxy_tts_listen<T: Transport + 'static>(
+    send: Sender<Captured>,
+    pipes_connected: Arc<AtomicUsize>,
+    controllers: Controllers,
 ) {
-    loop {
-        match tts_air_ipc::NamedPipe::open(tts_air_ipc::WARTIDE_ADDRESS) {
-            Ok(mut pipe) => {
-                log::info!("connected to text-to-speech capture at {:?}", tts_air_ipc::WARTIDE_ADDRESS);
-                pipe_connected.store(true, Ordering::Relaxed);
-
-                let mut text = Vec::with_capacity(0x10000);
-                let mut buffer = [0; 0x10000];
-                loop {
-                    match pipe.recv(&mut buffer) {
-                        Ok(read) => {
-                            let buffer = &buffer[..read as usize];
-                            for b in buffer {
-                                let b = *b;
-
-                                if b != 0 {
-                                    text.push(b);
-                                } else {
-                                    let text_s = String::from_utf8_lossy(&text);
-                                    log::debug!("tts string {text_s:?}");
-                                    if websocket_connected.load(Ordering::Relaxed) {
-                                        send.send(text_s.to_string()).unwrap();
-                                    }
-                                    text.clear();
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            log::trace!("failed connection read with error code {e:08x}");
-                            break;
-                        }
-                    }
+    thread::scope(|s| {
+        let mut instances = HashMap::new();
+        loop {
+            instances.retain(|_, instance: &mut thread::ScopedJoinHandle<()>| !instance.is_finished());
+            for (id, backend, address) in tts_air_ipc::discover::<T>() {
+                if instances.contains_key(&address) {
+                    continue;
                 }
+
+                log::debug!("found {} text-to-speech capture instance {id} at {address:?}", backend.name());
+                let send = send.clone();
+                let pipes_connected = pipes_connected.clone();
+                let controllers = controllers.clone();
+                let instance = {
+                    let address = address.clone();
+                    s.spawn(move || proxy_tts_instance::<T>(
+                        id,
+                        backend,
+                        &address,
+                        send,
+                        pipes_connected,
+                        controllers,
+                    ))
+                };
+                instances.insert(address, instance);
             }
-            Err(e) => log::trace!("failed connect to tts capture with error code 0x{e:08x}"),
+
+            thread::sleep(DISCOVER_INTERVAL);
         }
+    });
+}
+
+/// Forward events from the `backend` capture instance in process `id` until
+/// it is gone.
+fn proxy_tts_instance<T: Transport + 'static>(
+    id: u32,
+    backend: Backend,
+    address: &str,
+    send: Sender<Captured>,
+    pipes_connected: Arc<AtomicUsize>,
+    controllers: Controllers,
+) {
+    let client = TtsClient::<T>::with_transport(NAME)
+        .with_address(address)
+        .with_replay_last(REPLAY_LAST)
+        .with_retry_limit(RETRY_LIMIT)
+        .on_state(move |state| match state {
+            ConnectionState::Connected(_) => {
+                pipes_connected.fetch_add(1, Ordering::Relaxed);
+            }
+            ConnectionState::Disconnected(_) => {
+                pipes_connected.fetch_sub(1, Ordering::Relaxed);
+            }
+        });
+
+    let instance = Instance {
+        controller: client.controller(),
+        replay: Default::default(),
+    };
+    controllers.lock().unwrap().insert((id, backend), instance.clone());
+    for event in client {
+        log::debug!("tts event from instance {id} #{} {:?}", event.seq, event.kind);
+        let replay_to = if event.replayed { instance.replay.target() } else { None };
+        send.send((id, event, replay_to)).unwrap();
+    }
+    controllers.lock().unwrap().remove(&(id, backend));
+
+    log::debug!("text-to-speech capture instance {id} is gone");
+}
 
-        pipe_connected.store(false, Ordering::Relaxed);
-        thread::sleep(std::time::Duration::from_millis(500));
+/// Handshake check of [`proxy_ws_listen`]. Returns the origin of an allowed
+/// connection.
+// the handshake callback's error type is fixed by tungstenite
+#[allow(clippy::result_large_err)]
+fn check_origin(
+    req: &tungstenite::handshake::server::Request,
+    origins: &[OriginPattern],
+) -> Result<String, tungstenite::handshake::server::ErrorResponse> {
+    let req = req.headers();
+    let org = req.get("origin");
+    log::debug!("websocket connection headers:\n  user-agent: {:?}\n  host: {:?}\n  origin: {:?}",
+        req.get("user-agent"),
+        req.get("host"),
+        org,
+    );
+
+    if org.and_then(|o| o.to_str().ok())
+        .filter(|o| origin::allows(origins, o)
+            || (cfg!(debug_assertions) && cfg!(feature = "unsafe-connection")))
+        .is_some()
+    {
+        return Ok(org
+            .map(|h| h.to_str().unwrap_or("<invalid-str>"))
+            .unwrap_or("<null>").to_string());
     }
+
+    log::info!("denied websocket connection from origin {org:?}, \
+        allow it with --allow-origin or `origins` in {}", config::FILE_NAME);
+    Err(tungstenite::handshake::server::Response::builder()
+        .status(tungstenite::http::StatusCode::NOT_FOUND)
+        .body(None)
+        .unwrap()
+    )
 }
 
+// the handshake callback's error type is fixed by tungstenite
+#[allow(clippy::result_large_err)]
 fn proxy_ws_listen(
     server: TcpListener,
     send_ws: Sender<(tungstenite::WebSocket<NonblockingStream>, String)>,
+    origins: &[OriginPattern],
 ) {
     loop {
         let Ok((stream, _addr)) = server.accept() else {
@@ -202,35 +536,8 @@ fn proxy_ws_listen(
             req: &tungstenite::handshake::server::Request,
             res,
         | {
-            let req = req.headers();
-            let org = req.get("origin");
-            log::debug!("websocket connection headers:\n  user-agent: {:?}\n  host: {:?}\n  origin: {:?}",
-                req.get("user-agent"),
-                req.get("host"),
-                org,
-            );
-
-            origin = Some(org
-                .map(|h| h.to_str().unwrap_or("<invalid-str>"))
-                .unwrap_or("<null>").to_string());
-
-            if org.map(|o| o.to_str().ok())
-                .flatten()
-                .filter(|o| *o == "https://d4.wartide.net"
-                    // allow localhost connections
-                    || *o == "null"
-                    || (cfg!(debug_assertions) && cfg!(feature = "unsafe-connection")))
-                .is_some()
-            {
-                return Ok(res);
-            }
-
-            log::debug!("failed connection from origin {org:?}");
-            Err(tungstenite::handshake::server::Response::builder()
-                .status(tungstenite::http::StatusCode::NOT_FOUND)
-                .body(None)
-                .unwrap()
-            )
+            origin = Some(check_origin(req, origins)?);
+            Ok(res)
         });
 
         match res {
@@ -248,16 +555,25 @@ fn proxy_ws_listen(
 }
 
 fn proxy_ws_broadcast(
-    recv: Receiver<String>,
+    recv: Receiver<Captured>,
     recv_ws: Receiver<(tungstenite::WebSocket<NonblockingStream>, String)>,
-    pipe_connected: &AtomicBool,
-    websocket_connected: &AtomicBool,
+    pipes_connected: &AtomicUsize,
+    controllers: &Controllers,
+    history: usize,
+    mut filters: Filters,
+    record: Option<&Path>,
 ) {
+    let registry = methods::registry();
+    let mut shared = Shared::new(controllers.clone(), history);
+
     let mut is_connected = None;
     let mut json_state = String::new();
     let mut connections = Vec::new();
+    let mut next_id = 0;
+    // answers of requests by connection id
+    let (send_reply, recv_reply) = mpsc::channel::<(u64, String)>();
     loop {
-        let pipe_connected = pipe_connected.load(Ordering::Relaxed);
+        let pipe_connected = pipes_connected.load(Ordering::Relaxed) > 0;
         if is_connected != Some(pipe_connected) {
             is_connected = Some(pipe_connected);
             json_state = serde_json::json!({
@@ -270,23 +586,18 @@ fn proxy_ws_broadcast(
         }
 
         if let Ok((ws, origin)) = recv_ws.try_recv() {
+            next_id += 1;
             let mut wsc = WebSocketContext {
+                id: next_id,
+                session: Session::new(next_id),
                 ws,
                 origin,
                 pipe_notify_state: pipe_connected,
             };
 
-            'try_push: {
-                if let Err(e) = wsc.ws.write_message(tungstenite::Message::Text(json_state.to_owned())) {
-                    log::debug!("failed to update tts connection state to websocket with error {e:?}");
-                    break 'try_push;
-                }
-
-                if connections.is_empty() {
-                    while let Ok(_) = recv.try_recv() {}
-                    websocket_connected.store(true, Ordering::Relaxed);
-                }
-
+            if let Err(e) = wsc.ws.write_message(tungstenite::Message::Text(json_state.to_owned())) {
+                log::debug!("failed to update tts connection state to websocket with error {e:?}");
+            } else {
                 let total = connections.len() + 1;
                 let origin = &wsc.origin;
                 log::info!("websocket connect from {origin:?} ({total} total)");
@@ -295,70 +606,443 @@ fn proxy_ws_broadcast(
             }
         }
 
-        let text = recv.try_recv();
-        if !connections.is_empty() {
-            let text = text.ok().map(|text| {
-                serde_json::json!({
-                    "method": "tts_message",
-                    "args": {
-                        "message": text,
+        filters.reload();
+
+        // answers are taken first so the events replayed before the answer
+        // to a capture `replay` are in this batch or an earlier one
+        let replies = recv_reply.try_iter().collect::<Vec<_>>();
+
+        // events are kept for replay even while no one is connected
+        let mut events = Vec::new();
+        // events replayed by a capture instance for the connection with the id
+        let mut requested = Vec::new();
+        for (instance, event, replay_to) in recv.try_iter() {
+            if event.replayed {
+                let Some(id) = replay_to else {
+                    log::debug!("dropping replayed event #{} of instance {instance} after its request", event.seq);
+                    continue;
+                };
+                requested.extend(replayed_json(&shared, instance, &event).into_iter().map(|(method, json)| (id, method, json)));
+                continue;
+            }
+
+            let entry = shared.record(instance, &event, |seq| event_json(seq, instance, &event));
+            if let Some(item) = event_item(&event) {
+                if let (Some(dir), TtsEventKind::Message(text)) = (record, &event.kind) {
+                    match item::record(dir, text, &item) {
+                        Ok(path) => log::info!("recorded item tooltip to {path:?}"),
+                        Err(e) => log::warn!("failed to record item tooltip to {dir:?} with error {e:?}"),
                     }
-                }).to_string()
-            });
+                }
+                entry.derived.push(("item", item_json(entry.seq, instance, &event, &item)));
+                for m in filters.matches(&item) {
+                    entry.derived.push(("filter_match", filter_match_json(entry.seq, instance, &event, m)));
+                }
+            }
+            let seq = entry.seq;
+            events.extend(entry.messages().map(|(method, json)| (seq, method, json.to_string())));
+        }
+        shared.pipe_connected = pipe_connected;
+        shared.connections = connections.len();
 
-            connections.retain_mut(|wsc| {
-                match wsc.ws.read_message() {
-                    Ok(tungstenite::Message::Text(json)) => {
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&json) {
-                            if json["id"].is_i64() {
-                                let json = serde_json::json!({
-                                    "id": json["id"],
-                                    "data": "",
-                                });
-
-                                if let Err(_) = wsc.ws.write_message(tungstenite::Message::Text(json.to_string())) {
-                                    log::debug!("failed to send message to websocket connection");
-                                    return false;
-                                }
-                            } else {
-                                log::debug!("received json missing \"id\" field");
-                            }
-                        } else {
-                            log::debug!("expected json but received unknown");
-                        }
-                    },
-                    Ok(tungstenite::Message::Close(_)) => return false,
-                    Ok(_) => log::debug!("expected text from websocket connection"),
-                    Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => (),
-                    Err(e) => {
-                        log::debug!("failed websocket connection with error {e:?}");
+        connections.retain_mut(|wsc| {
+            match wsc.ws.read_message() {
+                Ok(tungstenite::Message::Text(json)) => {
+                    let (id, send_reply) = (wsc.id, send_reply.clone());
+                    registry.dispatch(&shared, &mut wsc.session, &json, move |reply| {
+                        let _ = send_reply.send((id, reply));
+                    });
+                },
+                Ok(tungstenite::Message::Close(_)) => return false,
+                Ok(_) => log::debug!("expected text from websocket connection"),
+                Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => (),
+                Err(e) => {
+                    log::debug!("failed websocket connection with error {e:?}");
+                    return false;
+                }
+            }
+
+            // replayed events go out before the answer of the request
+            for text in wsc.session.take_replayed() {
+                if let Err(e) = wsc.ws.write_message(tungstenite::Message::Text(text)) {
+                    log::debug!("failed to replay tts event with error {e:?}");
+                    return false;
+                }
+            }
+
+            for (_, method, text) in requested.iter().filter(|(id, ..)| *id == wsc.id) {
+                if wsc.session.is_subscribed(method) {
+                    if let Err(e) = wsc.ws.write_message(tungstenite::Message::Text(text.to_owned())) {
+                        log::debug!("failed to send replayed tts event with error {e:?}");
                         return false;
                     }
                 }
+            }
+
+            for (_, reply) in replies.iter().filter(|(id, _)| *id == wsc.id) {
+                if let Err(e) = wsc.ws.write_message(tungstenite::Message::Text(reply.to_owned())) {
+                    log::debug!("failed to send reply with error {e:?}");
+                    return false;
+                }
+            }
 
-                if let Some(text) = &text {
+            for (seq, method, text) in &events {
+                if wsc.session.wants(*seq, method) {
                     if let Err(e) = wsc.ws.write_message(tungstenite::Message::Text(text.to_owned())) {
                         log::debug!("failed to send tts event with error {e:?}");
                         return false;
                     }
                 }
+            }
 
-                if wsc.pipe_notify_state != pipe_connected {
+            if wsc.pipe_notify_state != pipe_connected {
+                if wsc.session.is_subscribed("info") {
                     if let Err(e) = wsc.ws.write_message(tungstenite::Message::Text(json_state.to_owned())) {
                         log::debug!("failed to update info with error {e:?}");
                         return false;
                     }
-                    wsc.pipe_notify_state = pipe_connected;
                 }
+                wsc.pipe_notify_state = pipe_connected;
+            }
 
-                true
-            });
+            true
+        });
+
+        thread::sleep(std::time::Duration::from_millis(5));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+    use std::sync::MutexGuard;
+    use std::sync::OnceLock;
+    use std::sync::PoisonError;
+    use std::time::Duration;
+
+    use tts_air_capture::Event;
+    use tts_air_ipc::Listener;
+    use tts_air_ipc::loopback;
+    use tts_air_ipc::protocol;
+    use tungstenite::client::IntoClientRequest;
+
+    use super::*;
+
+    type Client = tungstenite::WebSocket<TcpStream>;
 
-            if connections.is_empty() {
-                websocket_connected.store(false, Ordering::Relaxed);
+    /// Returns the connection and the latest `info` message.
+    fn connect(addr: SocketAddr) -> (Client, serde_json::Value) {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut request = format!("ws://{addr}/").into_client_request().unwrap();
+        request.headers_mut().insert("origin", "null".parse().unwrap());
+        let (mut ws, _) = tungstenite::client(request, stream).unwrap();
+        let mut info = next_json(&mut ws);
+
+        // the connection is registered once the proxy answers
+        ws.write_message(tungstenite::Message::Text(r#"{"id":1,"method":"ping"}"#.into())).unwrap();
+        loop {
+            let json = next_json(&mut ws);
+            if json["id"] == 1 {
+                break;
+            }
+            if json["method"] == "info" {
+                info = json;
             }
         }
+        (ws, info)
+    }
 
-        thread::sleep(std::time::Duration::from_millis(5));
+    fn next_json(ws: &mut Client) -> serde_json::Value {
+        loop {
+            if let tungstenite::Message::Text(json) = ws.read_message().unwrap() {
+                return serde_json::from_str(&json).unwrap();
+            }
+        }
+    }
+
+    /// Sends a request and waits for its answer.
+    fn request(ws: &mut Client, id: u64, method: &str, args: serde_json::Value) -> serde_json::Value {
+        let json = serde_json::json!({
+            "id": id,
+            "method": method,
+            "args": args,
+        });
+        ws.write_message(tungstenite::Message::Text(json.to_string())).unwrap();
+        answer(ws, id)
+    }
+
+    /// Skips other messages until the answer of request `id`.
+    fn answer(ws: &mut Client, id: u64) -> serde_json::Value {
+        loop {
+            let json = next_json(ws);
+            if json["id"] == id {
+                return json;
+            }
+        }
+    }
+
+    /// Skips `info` messages until the next tts event.
+    fn next_event(ws: &mut Client) -> serde_json::Value {
+        loop {
+            let json = next_json(ws);
+            if json["method"] != "info" {
+                return json;
+            }
+        }
     }
-}
\ No newline at end of file
+
+    fn say(text: &str) {
+        let text = text.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+        unsafe {
+            tts_air_capture::send_wchar(text.as_ptr(), Event::Say);
+        }
+    }
+
+    #[test]
+    fn origin_check() {
+        let request = |origin: Option<&str>| {
+            let mut request = tungstenite::http::Request::builder().uri("ws://127.0.0.1:61806/");
+            if let Some(origin) = origin {
+                request = request.header("origin", origin);
+            }
+            request.body(()).unwrap()
+        };
+
+        let mut config = Config::default();
+        config.allow_origin("--allow-origin", "http://*:5173").unwrap();
+        let check = |origin| check_origin(&request(origin), &config.origins).map_err(|res| res.status());
+        assert_eq!(check(Some("https://d4.wartide.net")).unwrap(), "https://d4.wartide.net");
+        assert_eq!(check(Some("null")).unwrap(), "null");
+        assert_eq!(check(Some("http://127.0.0.1:5173")).unwrap(), "http://127.0.0.1:5173");
+
+        if !cfg!(feature = "unsafe-connection") {
+            for origin in [None, Some("https://evil.com"), Some("http://127.0.0.1:5174")] {
+                assert_eq!(check(origin), Err(tungstenite::http::StatusCode::NOT_FOUND));
+            }
+        }
+    }
+
+    #[test]
+    fn port_in_use() {
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let e = bind(&[addr]).unwrap_err();
+        assert!(e.contains("another program"), "{e}");
+
+        let (send, recv) = mpsc::channel::<Captured>();
+        let (send_ws, recv_ws) = mpsc::channel();
+        thread::spawn(move || proxy_ws_listen(server, send_ws, &Config::default().origins));
+        thread::spawn(move || {
+            let filters = Filters::new(std::path::PathBuf::new());
+            proxy_ws_broadcast(recv, recv_ws, &AtomicUsize::new(0), &Controllers::default(), 1, filters, None)
+        });
+        let e = bind(&[addr]).unwrap_err();
+        assert!(e.contains(&format!("tts-air-proxy {}", env!("CARGO_PKG_VERSION"))), "{e}");
+        drop(send);
+    }
+
+    /// Address of the proxy connected to the capture library, which can only
+    /// be started once per process and is shared by the tests.
+    static PROXY: OnceLock<SocketAddr> = OnceLock::new();
+    /// Every connection sees the events of the other tests, so they take
+    /// turns.
+    static TURN: Mutex<()> = Mutex::new(());
+
+    fn rules_path() -> PathBuf {
+        std::env::temp_dir().join(format!("tts_air_filters_{}.toml", std::process::id()))
+    }
+
+    /// Waits for its turn and returns the address of the shared proxy.
+    fn proxy() -> (MutexGuard<'static, ()>, SocketAddr) {
+        let turn = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+        let addr = *PROXY.get_or_init(|| {
+            let config = tts_air_capture::Config::default();
+            assert!(tts_air_capture::start_with::<loopback::LoopbackListener>(&config, tts_air_capture::Backend::Saapi));
+
+            let pipes_connected = Arc::new(AtomicUsize::new(0));
+            let controllers = Controllers::default();
+            let (send, recv) = mpsc::channel();
+            let (send_ws, recv_ws) = mpsc::channel();
+            let server = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = server.local_addr().unwrap();
+
+            let (connected, instances) = (pipes_connected.clone(), controllers.clone());
+            thread::spawn(move || {
+                proxy_tts_listen::<loopback::LoopbackPipe>(send, connected, instances)
+            });
+            thread::spawn(move || proxy_ws_listen(server, send_ws, &Config::default().origins));
+            let history = Config::default().history;
+            std::fs::write(rules_path(), "[[rule]]\nname = 'legendary'\nwhen = { rarity = ['legendary'] }").unwrap();
+            let filters = Filters::new(rules_path());
+            thread::spawn(move || proxy_ws_broadcast(recv, recv_ws, &pipes_connected, &controllers, history, filters, None));
+
+            // only `info` messages are sent until the capture library is connected
+            let (mut ws, mut info) = connect(addr);
+            while info["args"]["is_connected"] != true {
+                info = next_json(&mut ws);
+            }
+            addr
+        });
+        (turn, addr)
+    }
+
+    #[test]
+    fn instance_discovery() {
+        let (_turn, addr) = proxy();
+        let (mut ws, info) = connect(addr);
+        assert_eq!(info["method"], "info");
+        assert_eq!(info["args"]["is_connected"], true);
+
+        let pid = std::process::id();
+        say("Harlequin Crest");
+        let json = next_event(&mut ws);
+        assert_eq!(json["method"], "tts_message");
+        assert_eq!(json["args"]["message"], "Harlequin Crest");
+        assert_eq!(json["args"]["backend"], "saapi");
+        assert_eq!(json["args"]["instance"], pid);
+        assert!(json["args"]["seq"].as_u64().unwrap() > 0);
+        assert!(json["args"]["timestamp"].as_u64().unwrap() > 1_600_000_000_000);
+
+        // a second game found while the proxy is running
+        let (done, wait) = mpsc::channel::<()>();
+        let other = thread::spawn(move || {
+            let mut listener = loopback::LoopbackListener::bind(&tts_air_ipc::instance_address(1, Backend::Nvda)).unwrap();
+            let mut pipe = listener.listen().unwrap();
+            let mut decoder = protocol::Decoder::new();
+            protocol::handshake_server(&mut pipe, &mut decoder, "other").unwrap();
+            let frame = protocol::Frame::new(protocol::Kind::Text, 1, 0, b"Tyrael's Might".to_vec());
+            protocol::send_frame(&mut pipe, &frame).unwrap();
+            let _ = wait.recv();
+        });
+        let json = next_event(&mut ws);
+        assert_eq!(json["args"]["message"], "Tyrael's Might");
+        assert_eq!(json["args"]["instance"], 1);
+        let json = request(&mut ws, 2, "get_status", serde_json::json!({}));
+        let instances = json["data"]["instances"].as_array().unwrap();
+        assert!(instances.contains(&pid.into()) && instances.contains(&1.into()), "{instances:?}");
+        drop(done);
+        other.join().unwrap();
+
+        // a message sent around a capture disconnect arrives once, either
+        // live or replayed on reconnect
+        loopback::disconnect(&tts_air_capture::Config::default().address(Backend::Saapi));
+        say("Shako");
+        assert_eq!(next_event(&mut ws)["args"]["message"], "Shako");
+        tts_air_capture::send_event(Event::Stop);
+        assert_eq!(next_event(&mut ws)["method"], "stop");
+    }
+
+    #[test]
+    fn rpc_dispatch() {
+        let (_turn, addr) = proxy();
+        let (mut ws, _) = connect(addr);
+
+        // commands to the capture library
+        let pid = std::process::id();
+        let capture = |command: serde_json::Value| {
+            let mut args = serde_json::json!({"instance": pid});
+            args.as_object_mut().unwrap().extend(command.as_object().unwrap().clone());
+            args
+        };
+        let json = request(&mut ws, 2, "capture", capture(serde_json::json!({"command": "ping"})));
+        assert_eq!(json["data"], "pong");
+        let json = request(&mut ws, 3, "capture", capture(serde_json::json!({"command": "version"})));
+        assert!(json["data"]["version"].as_str().unwrap().starts_with("tts-air-capture@"));
+        let json = request(&mut ws, 4, "capture", capture(serde_json::json!({"command": "status"})));
+        assert!(!json["data"]["clients"].as_array().unwrap().is_empty());
+        let args = capture(serde_json::json!({"command": "set_coalesce", "window_ms": 5000, "max_delay_ms": 5}));
+        assert_eq!(request(&mut ws, 5, "capture", args)["error"]["code"], rpc::RpcError::CAPTURE_ERROR);
+        let json = request(&mut ws, 6, "capture", serde_json::json!({"instance": 2, "command": "ping"}));
+        assert_eq!(json["error"]["message"], "unknown instance");
+        let args = serde_json::json!({"instance": pid, "backend": "saapi", "command": "ping"});
+        assert_eq!(request(&mut ws, 7, "capture", args)["data"], "pong");
+        let args = serde_json::json!({"instance": pid, "backend": "nvda", "command": "ping"});
+        assert_eq!(request(&mut ws, 7, "capture", args)["error"]["message"], "unknown instance");
+
+        // built-in methods
+        let json = request(&mut ws, 7, "get_status", serde_json::json!({}));
+        assert_eq!(json["data"]["is_connected"], true);
+        assert!(json["data"]["instances"].as_array().unwrap().contains(&pid.into()));
+        assert!(json["data"]["connections"].as_u64().unwrap() >= 1);
+        let json = request(&mut ws, 8, "nope", serde_json::json!({}));
+        assert_eq!(json["error"]["code"], rpc::RpcError::METHOD_NOT_FOUND);
+        let json = request(&mut ws, 9, "unsubscribe", serde_json::json!({"events": ["tts_message"]}));
+        assert_eq!(json["data"]["events"], serde_json::json!(["braille", "filter_match", "info", "item", "stop"]));
+        say("Unsubscribed");
+        tts_air_capture::send_event(Event::Stop);
+        assert_eq!(next_event(&mut ws)["method"], "stop");
+        request(&mut ws, 10, "subscribe", serde_json::json!({"events": ["tts_message"]}));
+        say("Subscribed");
+        assert_eq!(next_event(&mut ws)["args"]["message"], "Subscribed");
+    }
+
+    #[test]
+    fn history_and_replay() {
+        let (_turn, addr) = proxy();
+        let (mut watcher, _) = connect(addr);
+        say("Stormshield");
+        let json = next_event(&mut watcher);
+        assert_eq!(json["args"]["message"], "Stormshield");
+        let seq = json["args"]["seq"].as_u64().unwrap();
+
+        // events are kept for connections made after them
+        let (mut ws, _) = connect(addr);
+        let json = request(&mut ws, 2, "get_history", serde_json::json!({"limit": 1}));
+        assert_eq!(json["data"].as_array().unwrap().len(), 1);
+        assert_eq!(json["data"][0]["args"]["message"], "Stormshield");
+        // replayed events are sent before the answer
+        let json = serde_json::json!({"id": 3, "method": "replay", "args": {"after_seq": seq - 1}});
+        ws.write_message(tungstenite::Message::Text(json.to_string())).unwrap();
+        let json = next_event(&mut ws);
+        assert_eq!(json["args"]["message"], "Stormshield");
+        assert_eq!(json["replay"], true);
+        assert_eq!(answer(&mut ws, 3)["data"]["replayed"], 1);
+        let json = request(&mut ws, 4, "replay", serde_json::json!({"within_ms": 60_000}));
+        assert!(json["data"]["replayed"].as_u64().unwrap() >= 1);
+        let json = request(&mut ws, 5, "replay", serde_json::json!({}));
+        assert_eq!(json["error"]["code"], rpc::RpcError::INVALID_PARAMS);
+
+        // events replayed by the capture library are sent as recorded, only to
+        // the connection that asked and before the answer
+        let args = serde_json::json!({"instance": std::process::id(), "command": "replay", "last": 1});
+        let json = serde_json::json!({"id": 6, "method": "capture", "args": args});
+        ws.write_message(tungstenite::Message::Text(json.to_string())).unwrap();
+        let json = next_event(&mut ws);
+        assert_eq!(json["args"]["message"], "Stormshield");
+        assert_eq!(json["args"]["seq"], seq);
+        assert_eq!(json["replay"], true);
+        assert_eq!(answer(&mut ws, 6)["data"]["replayed"], 1);
+        assert_eq!(request(&mut watcher, 2, "ping", serde_json::json!({}))["data"], "pong");
+
+        // and are not recorded again
+        say("Shako");
+        let json = next_event(&mut ws);
+        assert_eq!(json["args"]["message"], "Shako");
+        assert_eq!(json["args"]["seq"], seq + 1);
+        assert!(json["replay"].is_null());
+    }
+
+    #[test]
+    fn filter_match() {
+        let (_turn, addr) = proxy();
+        let (mut ws, _) = connect(addr);
+
+        say("Ancestral Legendary Helm");
+        let json = next_event(&mut ws);
+        assert_eq!(json["method"], "tts_message");
+        let seq = json["args"]["seq"].clone();
+        // the tooltip parsed as an item follows the text
+        let json = next_event(&mut ws);
+        assert_eq!(json["method"], "item");
+        assert_eq!(json["args"]["rarity"], "legendary");
+        assert_eq!(json["args"]["item_type"], "Helm");
+        assert_eq!(json["args"]["seq"], seq);
+        let json = next_event(&mut ws);
+        assert_eq!(json["method"], "filter_match");
+        assert_eq!(json["args"]["rule"], "legendary");
+        assert_eq!(json["args"]["terms"], serde_json::json!([{"rarity": "legendary"}]));
+        assert_eq!(json["args"]["seq"], seq);
+    }
+}
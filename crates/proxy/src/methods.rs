@@ -0,0 +1,398 @@
+//! Methods WebSocket clients can call, see [`rpc`](crate::rpc).
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::thread;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use tts_air_ipc::client::TtsEvent;
+use tts_air_ipc::client::TtsEventKind;
+use tts_air_ipc::control::Command;
+use tts_air_ipc::control::Response;
+use tts_air_ipc::protocol;
+use tts_air_ipc::protocol::Backend;
+use tts_air_ipc::protocol::Replay;
+
+use crate::Controllers;
+use crate::REQUEST_TIMEOUT;
+use crate::rpc::Registry;
+use crate::rpc::Reply;
+use crate::rpc::RpcError;
+
+/// Methods of the messages sent to WebSocket clients without a request.
+pub const EVENTS: &[&str] = &["tts_message", "item", "filter_match", "braille", "stop", "info"];
+
+/// Method of the message sent for an event of `kind`.
+pub fn event_method(kind: &TtsEventKind) -> &'static str {
+    match kind {
+        TtsEventKind::Message(_) => "tts_message",
+        TtsEventKind::Braille(_) => "braille",
+        TtsEventKind::Stop => "stop",
+    }
+}
+
+/// Event kept for replay.
+pub struct Entry {
+    /// Sequence id across every capture instance, starting at 1.
+    pub seq: u64,
+    /// Process id of the capture instance.
+    pub instance: u32,
+    /// Backend of the capture instance, since a game can have one for each.
+    pub backend: Backend,
+    /// Sequence id of the event in its capture instance.
+    pub capture_seq: u64,
+    /// Capture time in microseconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub method: &'static str,
+    pub json: serde_json::Value,
+    /// Messages about the event by method, like the `item` of a tooltip.
+    pub derived: Vec<(&'static str, serde_json::Value)>,
+}
+
+impl Entry {
+    /// The event followed by the messages derived from it.
+    pub fn messages(&self) -> impl Iterator<Item = (&'static str, &serde_json::Value)> {
+        std::iter::once((self.method, &self.json)).chain(self.derived.iter().map(|(method, json)| (*method, json)))
+    }
+}
+
+/// Connection that asked a capture instance for a replay, so the replayed
+/// events only go to it.
+#[derive(Default)]
+pub struct ReplayRoute {
+    /// Held for the whole request so replays of one instance don't overlap.
+    busy: Mutex<()>,
+    to: Mutex<Option<u64>>,
+}
+
+impl ReplayRoute {
+    /// Run the replay `request` of connection `id`.
+    ///
+    /// The instance sends the replayed events before its answer and both are
+    /// read by the same thread, so every replayed event is routed before
+    /// `request` returns.
+    pub fn run<R>(&self, id: u64, request: impl FnOnce() -> R) -> R {
+        let _busy = self.busy.lock().unwrap();
+        *self.to.lock().unwrap() = Some(id);
+        let res = request();
+        *self.to.lock().unwrap() = None;
+        res
+    }
+
+    /// Connection the replayed events read now are for.
+    pub fn target(&self) -> Option<u64> {
+        *self.to.lock().unwrap()
+    }
+}
+
+/// State of the broadcast thread that every request can read.
+pub struct Shared {
+    pub pipe_connected: bool,
+    pub connections: usize,
+    pub controllers: Controllers,
+    /// Most recent events, oldest first.
+    history: VecDeque<Entry>,
+    capacity: usize,
+    seq: u64,
+}
+
+impl Shared {
+    /// Keep up to `capacity` events, which needs to be at least 1.
+    pub fn new(controllers: Controllers, capacity: usize) -> Self {
+        debug_assert!(capacity > 0);
+        Self {
+            pipe_connected: false,
+            connections: 0,
+            controllers,
+            history: VecDeque::with_capacity(capacity),
+            capacity,
+            seq: 0,
+        }
+    }
+
+    /// Add `event` of `instance` with the message `json` makes for its
+    /// sequence id.
+    pub fn record(
+        &mut self,
+        instance: u32,
+        event: &TtsEvent,
+        json: impl FnOnce(u64) -> serde_json::Value,
+    ) -> &mut Entry {
+        self.seq += 1;
+        if self.history.len() >= self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(Entry {
+            seq: self.seq,
+            instance,
+            backend: event.backend,
+            capture_seq: event.seq,
+            timestamp: event.timestamp,
+            method: event_method(&event.kind),
+            json: json(self.seq),
+            derived: Vec::new(),
+        });
+        self.history.back_mut().unwrap()
+    }
+
+    /// Entry of the event `capture_seq` of the `backend` capture instance in
+    /// process `instance` if it is still kept.
+    pub fn find(&self, instance: u32, backend: Backend, capture_seq: u64) -> Option<&Entry> {
+        self.history.iter().rev().find(|entry| {
+            (entry.instance, entry.backend, entry.capture_seq) == (instance, backend, capture_seq)
+        })
+    }
+}
+
+/// State of one WebSocket connection.
+pub struct Session {
+    /// Id of the connection.
+    id: u64,
+    /// Events sent to the connection, all of [`EVENTS`] at first.
+    subscriptions: BTreeSet<&'static str>,
+    /// Events queued by `replay`.
+    replayed: Vec<String>,
+    /// Last sequence id replayed, so live events aren't sent twice.
+    replayed_seq: u64,
+}
+
+impl Session {
+    pub fn new(id: u64) -> Self {
+        Self {
+            id,
+            subscriptions: EVENTS.iter().copied().collect(),
+            replayed: Vec::new(),
+            replayed_seq: 0,
+        }
+    }
+
+    pub fn is_subscribed(&self, event: &str) -> bool {
+        self.subscriptions.contains(event)
+    }
+
+    /// Whether the live event `seq` of kind `method` is sent to the connection.
+    pub fn wants(&self, seq: u64, method: &str) -> bool {
+        seq > self.replayed_seq && self.is_subscribed(method)
+    }
+
+    /// Events to send before anything else.
+    pub fn take_replayed(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.replayed)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoArgs {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HistoryArgs {
+    /// Only the most recent events.
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplayArgs {
+    /// Events after this sequence id.
+    after_seq: Option<u64>,
+    /// Events captured in the last given milliseconds.
+    within_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventArgs {
+    events: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CaptureArgs {
+    /// Process id of the game, can be left out while a single game is running.
+    instance: Option<u32>,
+    /// Backend of the capture library, can be left out while the game only
+    /// loaded one.
+    backend: Option<String>,
+    #[serde(flatten)]
+    command: CaptureCommand,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum CaptureCommand {
+    Ping,
+    Version,
+    SetCoalesce {
+        window_ms: u64,
+        max_delay_ms: u64,
+    },
+    Replay {
+        last: Option<u64>,
+        since: Option<u64>,
+    },
+    SetLogLevel {
+        level: String,
+    },
+    Status,
+}
+
+impl TryFrom<CaptureCommand> for Command {
+    type Error = RpcError;
+
+    fn try_from(command: CaptureCommand) -> Result<Self, Self::Error> {
+        Ok(match command {
+            CaptureCommand::Ping => Command::Ping,
+            CaptureCommand::Version => Command::Version,
+            CaptureCommand::SetCoalesce { window_ms, max_delay_ms } => Command::SetCoalesce { window_ms, max_delay_ms },
+            CaptureCommand::Replay { last: Some(last), since: None } => Command::Replay(Replay::Last(last)),
+            CaptureCommand::Replay { last: None, since: Some(seq) } => Command::Replay(Replay::Since(seq)),
+            CaptureCommand::Replay { .. } => {
+                return Err(RpcError::invalid_params("replay needs either \"last\" or \"since\""));
+            }
+            CaptureCommand::SetLogLevel { level } => match level.parse() {
+                Ok(level) => Command::SetLogLevel(level),
+                Err(_) => return Err(RpcError::invalid_params(format!("unknown level {level:?}"))),
+            },
+            CaptureCommand::Status => Command::Status,
+        })
+    }
+}
+
+fn subscribe(session: &mut Session, args: EventArgs, subscribe: bool) -> Reply {
+    for event in &args.events {
+        let Some(event) = EVENTS.iter().find(|e| *e == event) else {
+            return Err(RpcError::invalid_params(format!("unknown event {event:?}")));
+        };
+        if subscribe {
+            session.subscriptions.insert(event);
+        } else {
+            session.subscriptions.remove(event);
+        }
+    }
+
+    Ok(serde_json::json!({
+        "events": session.subscriptions,
+    }))
+}
+
+fn replay(shared: &Shared, session: &mut Session, args: ReplayArgs) -> Reply {
+    let keep: Box<dyn Fn(&Entry) -> bool> = match (args.after_seq, args.within_ms) {
+        (Some(seq), None) => Box::new(move |entry| entry.seq > seq),
+        (None, Some(ms)) => {
+            let since = protocol::timestamp(SystemTime::now()).saturating_sub(ms * 1000);
+            Box::new(move |entry| entry.timestamp >= since)
+        }
+        _ => return Err(RpcError::invalid_params("replay needs either \"after_seq\" or \"within_ms\"")),
+    };
+
+    let mut count = 0;
+    for entry in &shared.history {
+        if !keep(entry) {
+            continue;
+        }
+
+        for (method, json) in entry.messages() {
+            if session.is_subscribed(method) {
+                let mut json = json.clone();
+                json["replay"] = true.into();
+                session.replayed.push(json.to_string());
+                count += 1;
+            }
+        }
+    }
+    session.replayed_seq = shared.seq;
+
+    Ok(serde_json::json!({
+        "replayed": count,
+        "seq": shared.seq,
+    }))
+}
+
+fn capture_reply(response: Result<Response, tts_air_ipc::protocol::ProtocolError>) -> Reply {
+    match response {
+        Ok(Response::Pong) => Ok("pong".into()),
+        Ok(Response::Version { version, process }) => Ok(serde_json::json!({
+            "version": version,
+            "process": process,
+        })),
+        Ok(Response::Done) => Ok("done".into()),
+        Ok(Response::Replayed(count)) => Ok(serde_json::json!({
+            "replayed": count,
+        })),
+        Ok(Response::Status(clients)) => Ok(serde_json::json!({
+            "clients": clients.iter().map(|client| serde_json::json!({
+                "id": client.id,
+                "queued": client.queued,
+                "dropped": client.dropped,
+            })).collect::<Vec<_>>(),
+        })),
+        Ok(Response::Error(e)) => Err(RpcError::new(RpcError::CAPTURE_ERROR, e)),
+        Err(e) => Err(RpcError::new(RpcError::CAPTURE_ERROR, format!("capture request failed: {e}"))),
+    }
+}
+
+pub fn registry() -> Registry<Shared, Session> {
+    let mut registry = Registry::new();
+    registry
+        .method("ping", |_, _, _: NoArgs| Ok("pong".into()))
+        .method("get_status", |shared: &Shared, _, _: NoArgs| {
+            let mut instances = shared.controllers.lock().unwrap().keys().map(|(pid, _)| *pid).collect::<Vec<_>>();
+            instances.sort();
+            instances.dedup();
+            Ok(serde_json::json!({
+                "proxy_version": env!("CARGO_PKG_VERSION"),
+                "is_connected": shared.pipe_connected,
+                "instances": instances,
+                "connections": shared.connections,
+                "seq": shared.seq,
+            }))
+        })
+        .method("get_history", |shared: &Shared, _, args: HistoryArgs| {
+            let limit = args.limit.unwrap_or(usize::MAX);
+            let skip = shared.history.len().saturating_sub(limit);
+            Ok(shared.history.iter().skip(skip).flat_map(Entry::messages).map(|(_, json)| json.clone()).collect())
+        })
+        .method("replay", replay)
+        .method("subscribe", |_, session, args| subscribe(session, args, true))
+        .method("unsubscribe", |_, session, args| subscribe(session, args, false))
+        .deferred("capture", |shared: &Shared, session: &mut Session, args: CaptureArgs, responder| {
+            let command = match Command::try_from(args.command) {
+                Ok(command) => command,
+                Err(e) => return responder.send(Err(e)),
+            };
+
+            let instance = {
+                let controllers = shared.controllers.lock().unwrap();
+                let mut matching = controllers.iter()
+                    .filter(|((pid, backend), _)| {
+                        args.instance.is_none_or(|instance| instance == *pid)
+                            && args.backend.as_ref().is_none_or(|name| name == backend.name())
+                    })
+                    .map(|(_, instance)| instance);
+                match (matching.next(), matching.next()) {
+                    (Some(instance), None) => instance.clone(),
+                    (None, _) if args.instance.is_some() || args.backend.is_some() => {
+                        return responder.send(Err(RpcError::invalid_params("unknown instance")));
+                    }
+                    _ if args.instance.is_none() => {
+                        return responder.send(Err(RpcError::invalid_params("missing \"instance\"")));
+                    }
+                    _ => return responder.send(Err(RpcError::invalid_params("missing \"backend\""))),
+                }
+            };
+
+            // the capture library can take a while to answer
+            let id = session.id;
+            thread::spawn(move || {
+                let request = || instance.controller.request(&command, REQUEST_TIMEOUT);
+                let response = match command {
+                    Command::Replay(_) => instance.replay.run(id, request),
+                    _ => request(),
+                };
+                responder.send(capture_reply(response));
+            });
+        });
+    registry
+}
@@ -0,0 +1,186 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// Allowed value of the `Origin` header of a WebSocket handshake.
+///
+/// Either `null` or `scheme://host[:port]` where the scheme can be `*`, the
+/// host can be `*` or start with `*.` to match any subdomain, and the port
+/// can be `*`. Without a port only origins without one match.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum OriginPattern {
+    /// Sent by local files and sandboxed pages.
+    Null,
+    Url {
+        /// `None` matches any scheme.
+        scheme: Option<String>,
+        host: Host,
+        port: Port,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Host {
+    Any,
+    /// Any subdomain of the given domain but not the domain itself.
+    SubdomainOf(String),
+    Exact(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Port {
+    /// Origin has no port.
+    None,
+    Any,
+    Exact(u16),
+}
+
+/// Split an origin into scheme, host and port.
+fn split(origin: &str) -> Option<(&str, &str, Option<&str>)> {
+    let (scheme, rest) = origin.split_once("://")?;
+    let (host, port) = if rest.starts_with('[') {
+        // IPv6 address
+        let end = rest.find(']')? + 1;
+        match &rest[end..] {
+            "" => (&rest[..end], None),
+            port => (&rest[..end], Some(port.strip_prefix(':')?)),
+        }
+    } else {
+        match rest.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (rest, None),
+        }
+    };
+
+    let valid_scheme = !scheme.is_empty() && scheme.bytes().all(|b| b.is_ascii_alphanumeric() || b"+-.*".contains(&b));
+    if !valid_scheme || host.is_empty() || host.contains(['/', '?', '#', '@']) {
+        return None;
+    }
+    Some((scheme, host, port))
+}
+
+impl FromStr for OriginPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "null" {
+            return Ok(OriginPattern::Null);
+        }
+
+        let Some((scheme, host, port)) = split(s) else {
+            return Err(format!("{s:?} is not `null` or an origin like https://d4.wartide.net"));
+        };
+
+        let scheme = match scheme {
+            "*" => None,
+            scheme if !scheme.contains('*') => Some(scheme.to_ascii_lowercase()),
+            _ => return Err(format!("{s:?} can only use `*` as the whole scheme")),
+        };
+        let host = match host.strip_prefix("*.") {
+            _ if host == "*" => Host::Any,
+            Some(domain) if !domain.contains('*') => Host::SubdomainOf(domain.to_ascii_lowercase()),
+            None if !host.contains('*') => Host::Exact(host.to_ascii_lowercase()),
+            _ => return Err(format!("{s:?} can only use `*` as the whole host or its first label")),
+        };
+        let port = match port {
+            None => Port::None,
+            Some("*") => Port::Any,
+            Some(port) => Port::Exact(port.parse().map_err(|_| format!("{s:?} has an invalid port"))?),
+        };
+
+        Ok(OriginPattern::Url { scheme, host, port })
+    }
+}
+
+impl TryFrom<String> for OriginPattern {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl OriginPattern {
+    pub fn matches(&self, origin: &str) -> bool {
+        let OriginPattern::Url { scheme: p_scheme, host: p_host, port: p_port } = self else {
+            return origin == "null";
+        };
+        let Some((scheme, host, port)) = split(origin) else {
+            return false;
+        };
+
+        let scheme_matches = p_scheme.as_ref().is_none_or(|s| s.eq_ignore_ascii_case(scheme));
+        let host = host.to_ascii_lowercase();
+        let host_matches = match p_host {
+            Host::Any => true,
+            Host::SubdomainOf(domain) => host.strip_suffix(domain.as_str())
+                .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            Host::Exact(exact) => host == *exact,
+        };
+        let port_matches = match (p_port, port) {
+            (Port::None, None) | (Port::Any, _) => true,
+            (Port::Exact(p), Some(port)) => port.parse() == Ok(*p),
+            _ => false,
+        };
+
+        scheme_matches && host_matches && port_matches
+    }
+}
+
+/// Whether any of `patterns` matches `origin`.
+pub fn allows(patterns: &[OriginPattern], origin: &str) -> bool {
+    patterns.iter().any(|p| p.matches(origin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> OriginPattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn exact() {
+        let p = pattern("https://d4.wartide.net");
+        assert!(p.matches("https://d4.wartide.net"));
+        assert!(p.matches("HTTPS://D4.Wartide.net"));
+        assert!(!p.matches("http://d4.wartide.net"));
+        assert!(!p.matches("https://d4.wartide.net:8443"));
+        assert!(!p.matches("https://d4.wartide.net.evil.com"));
+        assert!(!p.matches("null"));
+
+        assert!(pattern("null").matches("null"));
+        assert!(!pattern("null").matches("https://d4.wartide.net"));
+        assert!(pattern("http://[::1]:3000").matches("http://[::1]:3000"));
+    }
+
+    #[test]
+    fn wildcards() {
+        let p = pattern("*://localhost:*");
+        assert!(p.matches("http://localhost"));
+        assert!(p.matches("https://localhost:3000"));
+        assert!(!p.matches("http://localhost.evil.com"));
+
+        let p = pattern("https://*.wartide.net");
+        assert!(p.matches("https://d4.wartide.net"));
+        assert!(p.matches("https://a.b.wartide.net"));
+        assert!(!p.matches("https://wartide.net"));
+        assert!(!p.matches("https://evilwartide.net"));
+
+        let p = pattern("http://*:5173");
+        assert!(p.matches("http://127.0.0.1:5173"));
+        assert!(!p.matches("http://127.0.0.1:5174"));
+        assert!(!p.matches("http://127.0.0.1"));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        for s in ["", "d4.wartide.net", "https://", "https://d4.wartide.net/app", "ht*p://x", "https://d*.net",
+            "https://*.*.net", "http://localhost:port", "http://[::1"]
+        {
+            assert!(s.parse::<OriginPattern>().is_err(), "{s:?}");
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Requests from WebSocket clients.
+//!
+//! A request is `{"id": 1, "method": "ping", "args": {...}}` where `args` can
+//! be left out. The answer keeps the `id` and has either `data` or an `error`
+//! with a JSON-RPC error `code` and a `message`. Messages without an `id` are
+//! ignored since they can't be answered.
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+pub type Reply = Result<Value, RpcError>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// Capture library failed or did not answer.
+    pub const CAPTURE_ERROR: i64 = -32000;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self::new(Self::INVALID_PARAMS, message)
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// Sends the answer of a request that finishes after its method returned.
+pub struct Responder {
+    id: Value,
+    send: Box<dyn FnOnce(String) + Send>,
+}
+
+impl Responder {
+    pub fn send(self, reply: Reply) {
+        (self.send)(answer(&self.id, reply));
+    }
+}
+
+fn answer(id: &Value, reply: Reply) -> String {
+    match reply {
+        Ok(data) => serde_json::json!({
+            "id": id,
+            "data": data,
+        }),
+        Err(e) => serde_json::json!({
+            "id": id,
+            "error": {
+                "code": e.code,
+                "message": e.message,
+            },
+        }),
+    }.to_string()
+}
+
+type Handler<S, C> = Box<dyn Fn(&S, &mut C, Value, Responder)>;
+
+/// Methods by name, called with state `S` shared by every connection and
+/// state `C` of the connection the request came from.
+pub struct Registry<S, C> {
+    methods: HashMap<&'static str, Handler<S, C>>,
+}
+
+impl<S, C> Registry<S, C> {
+    pub fn new() -> Self {
+        Self {
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Add method `name` answered with the result of `f`.
+    pub fn method<A, F>(&mut self, name: &'static str, f: F) -> &mut Self
+    where
+        A: DeserializeOwned,
+        F: Fn(&S, &mut C, A) -> Reply + 'static,
+    {
+        self.deferred(name, move |shared, conn, args, responder| responder.send(f(shared, conn, args)))
+    }
+
+    /// Add method `name` that answers through the [`Responder`] passed to
+    /// `f`, possibly from another thread.
+    pub fn deferred<A, F>(&mut self, name: &'static str, f: F) -> &mut Self
+    where
+        A: DeserializeOwned,
+        F: Fn(&S, &mut C, A, Responder) + 'static,
+    {
+        let handler = move |shared: &S, conn: &mut C, args: Value, responder: Responder| {
+            match serde_json::from_value(args) {
+                Ok(args) => f(shared, conn, args, responder),
+                Err(e) => responder.send(Err(RpcError::invalid_params(e.to_string()))),
+            }
+        };
+        self.methods.insert(name, Box::new(handler));
+        self
+    }
+
+    /// Call the method requested by `text`. The answer is passed to `send`
+    /// once it is ready.
+    pub fn dispatch(&self, shared: &S, conn: &mut C, text: &str, send: impl FnOnce(String) + Send + 'static) {
+        let request = match serde_json::from_str::<Value>(text) {
+            Ok(request) => request,
+            Err(e) => {
+                send(answer(&Value::Null, Err(RpcError::new(RpcError::PARSE_ERROR, e.to_string()))));
+                return;
+            }
+        };
+
+        let id = &request["id"];
+        if id.is_null() {
+            log::debug!("ignoring request without \"id\"");
+            return;
+        }
+        if !id.is_i64() {
+            send(answer(&Value::Null, Err(RpcError::new(RpcError::INVALID_REQUEST, "\"id\" needs to be an integer"))));
+            return;
+        }
+
+        let responder = Responder {
+            id: id.clone(),
+            send: Box::new(send),
+        };
+        let Some(method) = request["method"].as_str() else {
+            responder.send(Err(RpcError::new(RpcError::INVALID_REQUEST, "missing \"method\"")));
+            return;
+        };
+        let Some(handler) = self.methods.get(method) else {
+            responder.send(Err(RpcError::new(RpcError::METHOD_NOT_FOUND, format!("unknown method {method:?}"))));
+            return;
+        };
+
+        let args = match &request["args"] {
+            Value::Null => Value::Object(Default::default()),
+            args => args.clone(),
+        };
+        handler(shared, conn, args, responder);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Add {
+        n: i64,
+    }
+
+    fn call(registry: &Registry<i64, i64>, total: &mut i64, text: &str) -> Option<Value> {
+        let (send, recv) = mpsc::channel();
+        registry.dispatch(&1, total, text, move |answer| send.send(answer).unwrap());
+        recv.try_recv().ok().map(|answer| serde_json::from_str(&answer).unwrap())
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut registry = Registry::new();
+        registry.method("add", |step: &i64, total: &mut i64, args: Add| {
+            *total += args.n * step;
+            Ok((*total).into())
+        });
+
+        let mut total = 0;
+        assert_eq!(call(&registry, &mut total, r#"{"id":1,"method":"add","args":{"n":2}}"#).unwrap()["data"], 2);
+        assert_eq!(total, 2);
+
+        let code = |answer: Option<Value>| answer.unwrap()["error"]["code"].as_i64().unwrap();
+        assert_eq!(code(call(&registry, &mut total, r#"{"id":2,"method":"sub"}"#)), RpcError::METHOD_NOT_FOUND);
+        assert_eq!(code(call(&registry, &mut total, r#"{"id":3,"method":"add"}"#)), RpcError::INVALID_PARAMS);
+        let answer = r#"{"id":4,"method":"add","args":{"n":1,"m":1}}"#;
+        assert_eq!(code(call(&registry, &mut total, answer)), RpcError::INVALID_PARAMS);
+        assert_eq!(code(call(&registry, &mut total, r#"{"id":5}"#)), RpcError::INVALID_REQUEST);
+        assert_eq!(code(call(&registry, &mut total, r#"{"id":"6","method":"add"}"#)), RpcError::INVALID_REQUEST);
+        assert_eq!(code(call(&registry, &mut total, "{")), RpcError::PARSE_ERROR);
+        assert_eq!(call(&registry, &mut total, r#"{"method":"add","args":{"n":1}}"#), None);
+        assert_eq!(total, 2);
+    }
+}
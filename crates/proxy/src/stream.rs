@@ -83,7 +83,7 @@ impl Read for NonblockingStream {
                     }
                 }
             } else {
-                let slice = self.recv.recv().map_err(|_| io::Error::new(io::ErrorKind::Other, "Receiver error"))?;
+                let slice = self.recv.recv().map_err(|_| io::Error::other("Receiver error"))?;
                 let copy = total.min(slice.len());
                 buffer[..copy].copy_from_slice(&slice[..copy]);
                 Ok(copy)
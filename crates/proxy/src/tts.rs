@@ -1,45 +1,110 @@
+#[cfg(windows)]
+mod lib {
+    pub const NAME: &str = "saapi64.dll\0";
+
+    pub unsafe fn open(name: &str) -> isize {
+        windows_sys::Win32::System::LibraryLoader::LoadLibraryA(name.as_ptr() as *const _)
+    }
+
+    pub unsafe fn symbol(lib: isize, name: &str) -> Option<*const ()> {
+        windows_sys::Win32::System::LibraryLoader::GetProcAddress(lib, name.as_ptr() as *const _)
+            .map(|f| f as *const ())
+    }
+
+    pub unsafe fn close(lib: isize) {
+        windows_sys::Win32::System::LibraryLoader::FreeLibrary(lib);
+    }
+}
+
+#[cfg(unix)]
+mod lib {
+    pub const NAME: &str = "libsaapi64.so\0";
+
+    pub unsafe fn open(name: &str) -> isize {
+        libc::dlopen(name.as_ptr() as *const _, libc::RTLD_NOW) as isize
+    }
+
+    pub unsafe fn symbol(lib: isize, name: &str) -> Option<*const ()> {
+        let f = libc::dlsym(lib as *mut _, name.as_ptr() as *const _);
+        if f.is_null() {
+            None
+        } else {
+            Some(f as *const ())
+        }
+    }
+
+    pub unsafe fn close(lib: isize) {
+        libc::dlclose(lib as *mut _);
+    }
+}
+
+type WcharFn = unsafe extern "C" fn(*const u16) -> bool;
+type StopFn = unsafe extern "C" fn() -> bool;
+
 pub struct TtsAir {
     lib: isize,
-    tts: unsafe extern "C" fn(*const u16) -> bool,
+    tts: WcharFn,
+    braille: WcharFn,
+    stop: StopFn,
 }
 
 impl TtsAir {
     pub fn new() -> Self {
         let lib = unsafe {
-            windows_sys::Win32::System::LibraryLoader::LoadLibraryA("saapi64.dll\0".as_ptr() as *const _)
+            lib::open(lib::NAME)
         };
-        let tts: unsafe extern "C" fn(text: *const u16) -> bool = unsafe {
+        unsafe {
             assert_ne!(0, lib);
-            let running = windows_sys::Win32::System::LibraryLoader::GetProcAddress(lib, "SA_IsRunning\0".as_ptr() as *const _);
+            let running = lib::symbol(lib, "SA_IsRunning\0");
             let running: unsafe extern "C" fn() -> bool = core::mem::transmute(running.unwrap());
             running();
-            let tts = windows_sys::Win32::System::LibraryLoader::GetProcAddress(lib, "SA_SayW\0".as_ptr() as *const _);
-            core::mem::transmute(tts.unwrap())
-        };
+            let tts = lib::symbol(lib, "SA_SayW\0");
+            let braille = lib::symbol(lib, "SA_BrlShowTextW\0");
+            let stop = lib::symbol(lib, "SA_StopAudio\0");
 
-        Self {
-            lib,
-            tts,
+            Self {
+                lib,
+                tts: core::mem::transmute::<*const (), WcharFn>(tts.unwrap()),
+                braille: core::mem::transmute::<*const (), WcharFn>(braille.unwrap()),
+                stop: core::mem::transmute::<*const (), StopFn>(stop.unwrap()),
+            }
         }
     }
 
     pub fn say(&mut self, text: &str) -> bool {
-        let mut wchar = Vec::with_capacity(text.len());
-        for c in text.encode_utf16() {
-            wchar.push(c);
+        let wchar = to_wchar(text);
+        unsafe {
+            (self.tts)(wchar.as_ptr())
         }
-        wchar.push(0);
+    }
 
+    pub fn braille(&mut self, text: &str) -> bool {
+        let wchar = to_wchar(text);
         unsafe {
-            (self.tts)(wchar.as_ptr())
+            (self.braille)(wchar.as_ptr())
         }
     }
+
+    pub fn stop(&mut self) -> bool {
+        unsafe {
+            (self.stop)()
+        }
+    }
+}
+
+fn to_wchar(text: &str) -> Vec<u16> {
+    let mut wchar = Vec::with_capacity(text.len());
+    for c in text.encode_utf16() {
+        wchar.push(c);
+    }
+    wchar.push(0);
+    wchar
 }
 
 impl Drop for TtsAir {
     fn drop(&mut self) {
         unsafe {
-            windows_sys::Win32::System::LibraryLoader::FreeLibrary(self.lib);
+            lib::close(self.lib);
         }
     }
 }